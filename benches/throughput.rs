@@ -0,0 +1,60 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use mrakdown_engine::{
+    mapper::Mapper,
+    options::{MathMode, Options},
+    parser::{Parser, ParserImpl},
+    schema::HtmlSchema,
+    str_parser::StrParser,
+    tokenizer::{StrTokenizer, Tokenizer},
+};
+
+// 约 1 MB 的文档，中英文段落、标题、引用、列表、代码块和公式混排
+fn corpus() -> String {
+    let section = "# 第一章 Getting started\n\n\
+        Markdown 是一种轻量级标记语言，*强调*、**加粗**和 `行内代码` 都很常见。\
+        This paragraph mixes English text with [links](https://example.com) and plain words \
+        so that most of the input is ordinary text between special characters.\n\
+        第二行接着上一行，开启软换行时两行之间不加空格。\n\n\
+        ## 小节 {#section .note}\n\n\
+        > [!NOTE]\n> 提示框中的内容也按普通块处理，包括 *行内* 元素。\n\n\
+        - 列表项一\n- 列表项二 with some English\n- 列表项三 😊\n\n\
+        ```rust\nfn main() {\n    println!(\"# not a heading\");\n}\n```\n\n\
+        $$\nE = mc^2\n$$\n\n\
+        最后一段没有任何特殊字符只是一段比较长的中文文本用来测量纯文本的扫描速度。\n\n";
+    section.repeat(1024 * 1024 / section.len() + 1)
+}
+
+fn render<P: Parser>(mut parser: P, options: &Options) -> Vec<u8> {
+    let mut out = Vec::new();
+    parser.parse_and_write_with(Mapper::new(HtmlSchema::new(), &mut out), options).unwrap();
+    out
+}
+
+fn bench_throughput(c: &mut Criterion) {
+    let src = corpus();
+    let options = Options {
+        math: MathMode::Passthrough,
+        soft_breaks: true,
+        ..Options::default()
+    };
+    let mut group = c.benchmark_group("throughput");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    group.sample_size(20);
+
+    group.bench_function("tokenize_reader", |b| {
+        b.iter(|| Tokenizer::new(black_box(src.as_bytes())).count())
+    });
+    group.bench_function("tokenize_str", |b| {
+        b.iter(|| StrTokenizer::new(black_box(&src)).count())
+    });
+    group.bench_function("parse_reader", |b| {
+        b.iter(|| render(ParserImpl::new(black_box(src.as_bytes())), &options))
+    });
+    group.bench_function("parse_str", |b| {
+        b.iter(|| render(StrParser::new(black_box(&src)), &options))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_throughput);
+criterion_main!(benches);
@@ -6,9 +6,9 @@ use std::{
 };
 
 //使用&str的版本
-const BUF_SIZE: usize = 4;
+pub const BUF_SIZE: usize = 4;
 
-struct Chars<'a> {
+pub struct Chars<'a> {
     //实现一个迭代器，读取byte，返回一次返回一个char
     buf: [u8; BUF_SIZE],
     cursor: usize,
@@ -20,7 +20,7 @@ struct Chars<'a> {
 }
 
 impl<'a> Chars<'a> {
-    fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         Ok(Self {
             buf: [0; BUF_SIZE],
             cursor: 0,
@@ -45,13 +45,13 @@ impl<'a> Chars<'a> {
                 );
             }
             self.cursor += offset;
-            let chr = unsafe { std::str::from_utf8_unchecked(&(*(self as *const Self)).pre_buf[..char_len]) };
+            let chr = unsafe { std::str::from_utf8_unchecked(&(&(*(self as *const Self)).pre_buf)[..char_len]) };
             self.pre_len = 0;
             chr
         } else {
             let char_len = utf8_len(self.buf[self.cursor]);
             let chr = unsafe {
-                std::str::from_utf8_unchecked(&(*(self as *const Self)).buf[self.cursor..self.cursor + char_len])
+                std::str::from_utf8_unchecked(&(&(*(self as *const Self)).buf)[self.cursor..self.cursor + char_len])
             };
             self.cursor += char_len;
             chr
@@ -139,7 +139,7 @@ mod tests {
 
     #[test]
     fn utf8_char_works() {
-        let chars = Chars::new("123.txt").unwrap();
+        let chars = Chars::new("../123.txt").unwrap();
         for c in chars {
             println!("{:?}", c)
         }
@@ -0,0 +1,95 @@
+//! 缩写：`*[HTML]: Hyper Text Markup Language`，正文中完整出现的 `HTML`
+//! 会输出为 `<abbr title="Hyper Text Markup Language">HTML</abbr>`。
+use crate::units;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Abbreviation {
+    pub abbr: String,
+    pub title: String,
+}
+
+/// `*[abbr]: title`，最多缩进 3 个空格；更深的缩进或 tab 开头的行属于 `!!!` 提示框，不是定义
+pub fn parse_definition(line: &str) -> Option<Abbreviation> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 || !trimmed.starts_with("*[") {
+        return None;
+    }
+    let trimmed = units::trim_end(trimmed);
+    let close = units::bracket_end(trimmed, 1)?;
+    let abbr = units::trim(&trimmed[2..close]);
+    if abbr.is_empty() || trimmed.as_bytes().get(close + 1) != Some(&b':') {
+        return None;
+    }
+    Some(Abbreviation {
+        abbr: abbr.to_string(),
+        title: units::trim(&trimmed[close + 2..]).to_string(),
+    })
+}
+
+/// `text[i..]` 以某个缩写开头并且两侧都是词的边界时返回它，较长的缩写优先
+pub fn find_at<'a>(text: &str, i: usize, abbreviations: &'a [Abbreviation]) -> Option<&'a Abbreviation> {
+    if joins(units::char_before(text, i), units::char_at(text, i)) {
+        return None;
+    }
+    abbreviations
+        .iter()
+        .filter(|a| text[i..].starts_with(&a.abbr))
+        .filter(|a| {
+            let end = i + a.abbr.len();
+            !joins(units::char_before(text, end), units::char_at(text, end))
+        })
+        .max_by_key(|a| a.abbr.len())
+}
+
+// 两个字符是否属于同一个词；中文等非 ASCII 文字和英文直接相连时视为边界
+fn joins(a: Option<char>, b: Option<char>) -> bool {
+    let word = |c: char| c.is_alphanumeric() || c == '_';
+    matches!((a, b), (Some(a), Some(b)) if word(a) && word(b) && a.is_ascii() == b.is_ascii())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, parser::{tests::render_with, ParserImpl}};
+
+    #[test]
+    fn definitions_and_word_boundaries() {
+        let def = parse_definition("*[HTML]: Hyper Text Markup Language").unwrap();
+        assert_eq!(def.abbr, "HTML");
+        assert_eq!(def.title, "Hyper Text Markup Language");
+        assert!(parse_definition("*[HTML] no colon").is_none());
+        assert!(parse_definition("   *[CSS]: x").is_some());
+        assert!(parse_definition("    *[CSS]: x").is_none() && parse_definition("\t*[CSS]: x").is_none());
+
+        let abbrs = [def];
+        let text = "HTML5 XHTML HTML, 用HTML写";
+        let hits: Vec<usize> = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .filter(|&i| find_at(text, i, &abbrs).is_some())
+            .collect();
+        assert_eq!(hits, vec![12, 21]);
+    }
+
+    #[test]
+    fn abbreviations_defined_anywhere() {
+        let options = Options {
+            abbreviations: true,
+            ..Options::default()
+        };
+        let src = "# HTML 入门\nHTML5 and HTML, `HTML` [HTML](/h)\n```\n*[X]: not a definition\n```\n*[HTML]: Hyper \"Text\"\n\
+                   !!! note\n    *[CSS]: in a container\n> [!NOTE]\n> *[CSS]: quoted\n\nCSS\n";
+        let abbr = "<abbr title=\"Hyper &quot;Text&quot;\">HTML</abbr>";
+        assert_eq!(
+            render_with(ParserImpl::new(src.as_bytes()), &options),
+            format!(
+                "<h1>{abbr} 入门</h1><br><p>HTML5 and {abbr}, <code>HTML</code> <a href=\"/h\">HTML</a></p>\
+                 <pre><code>*[X]: not a definition\n</code></pre>\
+                 <div class=\"markdown-alert markdown-alert-note\"><p class=\"markdown-alert-title\">Note</p>\
+                 <p>*[CSS]: in a container</p></div>\
+                 <div class=\"markdown-alert markdown-alert-note\"><p class=\"markdown-alert-title\">Note</p>\
+                 <p>*[CSS]: quoted</p></div><br><p>CSS</p>"
+            )
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! 文档树：`AstParser::parse_to_ast` 解析出的 `Document`，可以检查、修改后再用
+//! `Document::render` 输出到任意 `Schema`/`Writer`。
+//!
+//! 解析器总是先解析出块再交给 `Mapper` 输出，流式输出只是每完成一个顶层块就输出一次。
+//! 标题 id、编号、目录和缩写在输出时才计算，变量在解析时已经替换。
+use std::io::Result as IoResult;
+
+use crate::{
+    abbr::Abbreviation,
+    attributes::Attributes,
+    directive::{Directive, DirectiveKind},
+    mapper::Mapper,
+    math::MathKind,
+    options::Options,
+    ruby::Ruby,
+    schema::Schema,
+    sourcemap::SourceMap,
+    writer::Writer,
+};
+
+/// 节点在源文件中的范围：块从第一行行首到最后一行行尾（不含换行符），行内节点精确到字符
+pub use crate::tokenizer::Span;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub blocks: Vec<Block>,
+    /// 整篇文档的缩写定义，输出文本时替换
+    pub abbreviations: Vec<Abbreviation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockKind {
+    Paragraph {
+        attrs: Attributes,
+        children: Vec<Inline>,
+    },
+    Heading {
+        level: u8,
+        attrs: Attributes,
+        children: Vec<Inline>,
+    },
+    CodeBlock {
+        lang: Option<String>,
+        attrs: Attributes,
+        code: String,
+    },
+    /// `$$` 公式块和 ```` ```math ```` 代码块
+    Math(String),
+    /// `> [!NOTE]` 和 `!!! note`
+    Alert {
+        kind: String,
+        title: Option<String>,
+        attrs: Attributes,
+        children: Vec<Block>,
+    },
+    /// `:::name` 容器指令
+    Directive(DirectiveNode<Block>),
+    /// `::name[label]` 独占一行的指令
+    LeafDirective(DirectiveNode<Inline>),
+    /// `[TOC]`
+    TocMarker,
+    /// 不开启软换行时的空行，输出为 `<br>`
+    BlankLine,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inline {
+    pub kind: InlineKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InlineKind {
+    Text(String),
+    Code(String),
+    /// `level` 与 `Mapper::write_html_emphasis_start` 相同
+    Emphasis {
+        level: u8,
+        children: Vec<Inline>,
+    },
+    Math {
+        kind: MathKind,
+        tex: String,
+    },
+    Emoji {
+        name: String,
+        emoji: String,
+    },
+    /// 普通链接以及自动链接、维基链接
+    Link {
+        dest: String,
+        title: Option<String>,
+        attrs: Attributes,
+        children: Vec<Inline>,
+    },
+    Image {
+        src: String,
+        alt: String,
+        title: Option<String>,
+        attrs: Attributes,
+    },
+    /// 基字和注音
+    Ruby(Vec<(String, String)>),
+    Directive(DirectiveNode<Inline>),
+}
+
+/// 指令的子节点：容器指令为其中的块，其它指令为 label 中的行内内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirectiveNode<C> {
+    pub name: String,
+    pub label: Option<String>,
+    pub attributes: Attributes,
+    pub children: Vec<C>,
+}
+
+impl Document {
+    /// 按 `options` 输出，与 `Parser::parse_and_write_with` 的结果相同
+    pub fn render<S: Schema, W: Writer>(&self, mut mapper: Mapper<S, W>, options: &Options) -> IoResult<()> {
+        mapper.apply_options(options);
+        mapper.set_abbreviations(self.abbreviations.clone());
+        mapper.claim_heading_ids(heading_ids(&self.blocks));
+        render_blocks(&self.blocks, &mut mapper, options)?;
+        mapper.flush()
+    }
+
+    /// 同 `render`，另外返回每个块的输出范围与源文件范围的对应关系
+    pub fn render_with_source_map<S: Schema, W: Writer>(
+        &self,
+        mut mapper: Mapper<S, W>,
+        options: &Options,
+    ) -> IoResult<SourceMap> {
+        mapper.enable_source_map();
+        mapper.apply_options(options);
+        mapper.set_abbreviations(self.abbreviations.clone());
+        mapper.claim_heading_ids(heading_ids(&self.blocks));
+        render_blocks(&self.blocks, &mut mapper, options)?;
+        mapper.flush()?;
+        Ok(mapper.take_source_map())
+    }
+}
+
+/// 手写的标题 id，包括容器中的标题
+pub(crate) fn heading_ids(blocks: &[Block]) -> Vec<&str> {
+    let mut ids = Vec::new();
+    let mut stack = vec![blocks];
+    while let Some(blocks) = stack.pop() {
+        for block in blocks {
+            match &block.kind {
+                BlockKind::Heading { attrs, .. } => ids.extend(attrs.id.as_deref()),
+                BlockKind::Alert { children, .. } => stack.push(children),
+                BlockKind::Directive(node) => stack.push(&node.children),
+                _ => {}
+            }
+        }
+    }
+    ids
+}
+
+pub(crate) fn render_blocks<S: Schema, W: Writer>(
+    blocks: &[Block],
+    mapper: &mut Mapper<S, W>,
+    options: &Options,
+) -> IoResult<()> {
+    for block in blocks {
+        mapper.set_line_span(block.span);
+        match &block.kind {
+            BlockKind::Paragraph { attrs, children } => {
+                mapper.write_html_paragraph_start(attrs)?;
+                render_inlines(children, mapper, options)?;
+                mapper.write_html_paragraph_end()?;
+            }
+            BlockKind::Heading { level, attrs, children } => {
+                mapper.write_html_header_start(*level, attrs)?;
+                render_inlines(children, mapper, options)?;
+                mapper.write_html_header_end(*level)?;
+                mapper.write_html_new_line()?;
+            }
+            BlockKind::CodeBlock { lang, attrs, code } => {
+                mapper.write_html_code_block_start(lang.as_deref(), attrs)?;
+                mapper.write_html_escaped(code)?;
+                mapper.write_html_code_block_end()?;
+            }
+            BlockKind::Math(tex) => mapper.write_html_math(tex, MathKind::Block, options.math)?,
+            BlockKind::Alert { kind, title, attrs, children } => {
+                let title = title.as_deref();
+                mapper.write_html_alert_start(kind, title, attrs)?;
+                render_blocks(children, mapper, options)?;
+                mapper.write_html_alert_end()?;
+            }
+            BlockKind::Directive(node) => {
+                render_directive(node, DirectiveKind::Container, mapper, options, render_blocks)?
+            }
+            BlockKind::LeafDirective(node) => {
+                render_directive(node, DirectiveKind::Leaf, mapper, options, render_inlines)?
+            }
+            BlockKind::TocMarker => mapper.write_html_toc_marker()?,
+            BlockKind::BlankLine => mapper.write_html_new_line()?,
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn render_inlines<S: Schema, W: Writer>(
+    inlines: &[Inline],
+    mapper: &mut Mapper<S, W>,
+    options: &Options,
+) -> IoResult<()> {
+    for inline in inlines {
+        match &inline.kind {
+            InlineKind::Text(text) => mapper.write_html_text(text)?,
+            InlineKind::Code(code) => mapper.write_html_code(code)?,
+            InlineKind::Emphasis { level, children } => {
+                mapper.write_html_emphasis_start(*level)?;
+                render_inlines(children, mapper, options)?;
+                mapper.write_html_emphasis_end(*level)?;
+            }
+            InlineKind::Math { kind, tex } => mapper.write_html_math(tex, *kind, options.math)?,
+            InlineKind::Emoji { name, emoji } => mapper.write_html_emoji(name, emoji, &options.emoji)?,
+            InlineKind::Link { dest, title, attrs, children } => {
+                mapper.write_html_link_start(dest, title.as_deref(), attrs)?;
+                render_inlines(children, mapper, options)?;
+                mapper.write_html_link_end()?;
+            }
+            InlineKind::Image { src, alt, title, attrs } => {
+                mapper.write_html_image(src, alt, title.as_deref(), attrs)?
+            }
+            InlineKind::Ruby(groups) => {
+                let ruby = Ruby {
+                    groups: groups.iter().map(|(base, text)| (base.as_str(), text.as_str())).collect(),
+                };
+                mapper.write_html_ruby(&ruby)?;
+            }
+            InlineKind::Directive(node) => {
+                render_directive(node, DirectiveKind::Text, mapper, options, render_inlines)?
+            }
+        }
+    }
+    Ok(())
+}
+
+type RenderFn<C, S, W> = fn(&[C], &mut Mapper<S, W>, &Options) -> IoResult<()>;
+
+// 先输出子节点，再连同属性交给注册的处理函数；没有注册的指令不输出
+fn render_directive<C, S: Schema, W: Writer>(
+    node: &DirectiveNode<C>,
+    kind: DirectiveKind,
+    mapper: &mut Mapper<S, W>,
+    options: &Options,
+    render_children: RenderFn<C, S, W>,
+) -> IoResult<()> {
+    let Some(handler) = options.directives.get(&node.name) else {
+        return Ok(());
+    };
+    mapper.begin_capture();
+    let rendered = render_children(&node.children, mapper, options);
+    let children = mapper.end_capture();
+    rendered?;
+    let directive = Directive {
+        kind,
+        name: &node.name,
+        label: node.label.as_deref(),
+        attributes: &node.attributes,
+        children: &children,
+    };
+    mapper.write_html_directive(handler, &directive)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        options::{HeadingIdOptions, MathMode},
+        parser::{AstParser, Parser, ParserImpl},
+        schema::HtmlSchema,
+        toc::TocOptions,
+    };
+
+    #[test]
+    fn tree_renders_like_streaming() {
+        let src = "[TOC]\n# 标题 {.big}\n\n> [!NOTE]\n> 见 [文档](https://a.com \"t\") 和 `code`\n\n\
+                   第一行 *强调*\n第二行 $x^2$\n\n```rust\nfn main() {}\n```\n";
+        let options = Options {
+            math: MathMode::Passthrough,
+            soft_breaks: true,
+            toc: Some(TocOptions::default()),
+            heading_ids: Some(HeadingIdOptions::default()),
+            ..Options::default()
+        };
+        let mut streamed = Vec::new();
+        ParserImpl::new(src.as_bytes())
+            .parse_and_write_with(Mapper::new(HtmlSchema::new(), &mut streamed), &options)
+            .unwrap();
+        let doc = ParserImpl::new(src.as_bytes()).parse_to_ast(&options).unwrap();
+        let mut rendered = Vec::new();
+        doc.render(Mapper::new(HtmlSchema::new(), &mut rendered), &options).unwrap();
+        assert_eq!(String::from_utf8(rendered).unwrap(), String::from_utf8(streamed).unwrap());
+
+        let lines = |span: Span| (span.start.line, span.end.line);
+        let kinds: Vec<(&str, (u32, u32))> = doc
+            .blocks
+            .iter()
+            .map(|b| {
+                let kind = match b.kind {
+                    BlockKind::TocMarker => "toc",
+                    BlockKind::Heading { .. } => "heading",
+                    BlockKind::BlankLine => "blank",
+                    BlockKind::Alert { .. } => "alert",
+                    BlockKind::Paragraph { .. } => "p",
+                    BlockKind::CodeBlock { .. } => "code",
+                    _ => "other",
+                };
+                (kind, lines(b.span))
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![("toc", (1, 1)), ("heading", (2, 2)), ("alert", (4, 5)), ("p", (7, 8)), ("code", (10, 12))]
+        );
+        // 块从行首到行尾，不含换行符
+        assert_eq!((doc.blocks[1].span.start.offset, doc.blocks[1].span.end.offset), (6, 21));
+
+        // 行内节点精确到列
+        let columns = |span: Span| (span.start.line, span.start.column, span.end.line, span.end.column);
+        let BlockKind::Alert { children, .. } = &doc.blocks[2].kind else { unreachable!() };
+        let BlockKind::Paragraph { children, .. } = &children[0].kind else { unreachable!() };
+        assert!(matches!(
+            &children[1].kind,
+            InlineKind::Link { dest, title: Some(title), .. } if dest == "https://a.com" && title == "t"
+        ));
+        assert_eq!(columns(children[1].span), (5, 5, 5, 28));
+        assert_eq!(columns(children[3].span), (5, 31, 5, 37));
+        let BlockKind::Paragraph { children, .. } = &doc.blocks[3].kind else { unreachable!() };
+        let spans: Vec<_> = children.iter().map(|inline| columns(inline.span)).collect();
+        assert_eq!(spans, vec![(7, 1, 7, 5), (7, 5, 7, 9), (7, 9, 8, 5), (8, 5, 8, 10)]);
+        let BlockKind::CodeBlock { code, .. } = &doc.blocks[4].kind else { unreachable!() };
+        assert_eq!(code, "fn main() {}\n");
+    }
+}
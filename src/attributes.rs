@@ -0,0 +1,203 @@
+//! `{#id .class key="value"}` 形式的属性列表
+use crate::{mapper::escape_html, units};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub pairs: Vec<(String, String)>,
+}
+
+impl Attributes {
+    pub fn is_empty(&self) -> bool {
+        self.id.is_none() && self.classes.is_empty() && self.pairs.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "id" => self.id.as_deref(),
+            _ => self
+                .pairs
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str()),
+        }
+    }
+
+    /// 合并另一组属性，`other` 中的 id 和同名属性优先
+    pub fn merge(&mut self, other: Attributes) {
+        if other.id.is_some() {
+            self.id = other.id;
+        }
+        self.classes.extend(other.classes);
+        for (key, value) in other.pairs {
+            match self.pairs.iter_mut().find(|(k, _)| *k == key) {
+                Some(pair) => pair.1 = value,
+                None => self.pairs.push((key, value)),
+            }
+        }
+    }
+
+    /// 输出为 ` id="x" class="a b" key="value"`，值已转义
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        if let Some(id) = &self.id {
+            out.push_str(&format!(" id=\"{}\"", escape_html(id)));
+        }
+        if !self.classes.is_empty() {
+            out.push_str(&format!(" class=\"{}\"", escape_html(&self.classes.join(" "))));
+        }
+        for (key, value) in &self.pairs {
+            out.push_str(&format!(" {}=\"{}\"", escape_html(key), escape_html(value)));
+        }
+        out
+    }
+}
+
+/// `text` 从 `{` 开始，返回解析出的属性以及包括 `}` 在内消耗的长度；
+/// 至少要有一个 `#id`、`.class` 或 `key=value`，`{}`、`{foo bar}` 不是属性列表
+pub fn parse(text: &str) -> Option<(Attributes, usize)> {
+    let bytes = text.as_bytes();
+    if bytes.first() != Some(&b'{') {
+        return None;
+    }
+    let mut attrs = Attributes::default();
+    let mut explicit = false;
+    let mut i = 1;
+    loop {
+        while bytes.get(i).is_some_and(|b| units::is_blank_byte(*b)) {
+            i += 1;
+        }
+        let byte = *bytes.get(i)?;
+        if byte == b'}' {
+            return explicit.then_some((attrs, i + 1));
+        }
+        explicit |= byte == b'#' || byte == b'.';
+        // `{-}` 是 `{.unnumbered}` 的简写，由标题编号处理
+        if byte == b'-' && bytes.get(i + 1).is_some_and(|b| units::is_blank_byte(*b) || *b == b'}') {
+            attrs.pairs.push(("-".into(), String::new()));
+            explicit = true;
+            i += 1;
+            continue;
+        }
+        if byte == b'#' || byte == b'.' {
+            let start = i + 1;
+            i = start + word_len(&text[start..]);
+            if i == start {
+                return None;
+            }
+            let word = text[start..i].to_string();
+            if byte == b'#' {
+                attrs.id = Some(word);
+            } else {
+                attrs.classes.push(word);
+            }
+            continue;
+        }
+        let start = i;
+        i += key_len(&text[i..]);
+        if i == start || !bytes.get(i).is_some_and(|b| units::is_blank_byte(*b) || *b == b'}' || *b == b'=') {
+            return None;
+        }
+        let key = text[start..i].to_string();
+        if bytes.get(i) == Some(&b'=') {
+            explicit = true;
+            i += 1;
+            let value = match *bytes.get(i)? {
+                quote @ (b'"' | b'\'') => {
+                    let close = text[i + 1..].find(quote as char)?;
+                    let value = text[i + 1..i + 1 + close].to_string();
+                    i += close + 2;
+                    value
+                }
+                _ => {
+                    let start = i;
+                    i += word_len(&text[i..]);
+                    text[start..i].to_string()
+                }
+            };
+            match key.as_str() {
+                "id" => attrs.id = Some(value),
+                "class" => attrs.classes.extend(value.split_whitespace().map(String::from)),
+                _ => attrs.pairs.push((key, value)),
+            }
+        } else {
+            attrs.pairs.push((key, String::new()));
+        }
+    }
+}
+
+/// 去掉行尾的 `{...}`，例如 `## 标题 {#id}`
+pub fn trailing(text: &str) -> Option<(&str, Attributes)> {
+    let trimmed = units::trim_end(text);
+    if !trimmed.ends_with('}') {
+        return None;
+    }
+    let start = trimmed.rfind('{')?;
+    let (attrs, len) = parse(&trimmed[start..])?;
+    if start + len != trimmed.len() {
+        return None;
+    }
+    Some((units::trim_end(&trimmed[..start]), attrs))
+}
+
+// 属性名：`[A-Za-z_:][A-Za-z0-9_.:-]*`
+fn key_len(text: &str) -> usize {
+    let first = |b: &u8| matches!(b, b'A'..=b'Z' | b'a'..=b'z' | b'_' | b':');
+    let rest = |b: &u8| first(b) || matches!(b, b'0'..=b'9' | b'.' | b'-');
+    match text.as_bytes().split_first() {
+        Some((b, tail)) if first(b) => 1 + tail.iter().take_while(|b| rest(b)).count(),
+        _ => 0,
+    }
+}
+
+fn word_len(text: &str) -> usize {
+    text.find([' ', '\t', '}', '{']).unwrap_or(text.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, parser::{tests::render_with, ParserImpl}};
+
+    #[test]
+    fn parse_attribute_list() {
+        let src = "{#intro .note .wide data-x=\"a b\" lang=zh hidden} rest";
+        let (attrs, len) = parse(src).unwrap();
+        assert_eq!(&src[len..], " rest");
+        assert_eq!(attrs.id.as_deref(), Some("intro"));
+        assert_eq!(attrs.classes, vec!["note", "wide"]);
+        assert_eq!(attrs.get("data-x"), Some("a b"));
+        assert_eq!(
+            attrs.to_html(),
+            " id=\"intro\" class=\"note wide\" data-x=\"a b\" lang=\"zh\" hidden=\"\""
+        );
+        assert!(parse("{#id").is_none());
+        for text in ["{}", "{foo bar}", "{\"a\":1}", "{a/b=1}", "{1a=x}"] {
+            assert!(parse(text).is_none(), "{}", text);
+        }
+
+        let (text, attrs) = trailing("安装 {x} 指南 {#install .big}").unwrap();
+        assert_eq!(text, "安装 {x} 指南");
+        assert_eq!(attrs.id.as_deref(), Some("install"));
+    }
+
+    #[test]
+    fn attribute_lists_reach_start_tags() {
+        let render = |src: &str| render_with(ParserImpl::new(src.as_bytes()), &Options::default());
+        assert_eq!(
+            render("## 安装指南 {#install .big}\n{.lead data-x=1}\nHello [a](/b){#l}\n```rust {.numberLines}\nx\n```\n~~~ {.py #s}\ny\n~~~\n"),
+            "<h2 id=\"install\" class=\"big\">安装指南</h2><br>\
+             <p class=\"lead\" data-x=\"1\">Hello <a href=\"/b\" id=\"l\">a</a></p>\
+             <pre class=\"numberLines\"><code class=\"language-rust\">x\n</code></pre>\
+             <pre id=\"s\"><code class=\"language-py\">y\n</code></pre>"
+        );
+        // 后面是空行时丢弃
+        assert_eq!(render("{#gone}\n\ntext"), "<br><p>text</p>");
+        // 不是属性列表的 `{...}` 行按段落输出
+        assert_eq!(
+            render("{}\n\n{foo bar}\n\n{\"a\":1}\n"),
+            "<p>{}</p><br><p>{foo bar}</p><br><p>{\"a\":1}</p>"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! 自动链接 `#123`、`org/repo#123`、`@username` 和 7 到 40 位的提交 SHA，
+//! URL 由引擎配置的模板生成，没有配置模板的类型不识别。
+
+use crate::units;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AutolinkOptions {
+    /// `#123` 和提交 SHA 所属的仓库，填入模板的 `{repo}`
+    pub repo: Option<String>,
+    /// 例如 `https://tracker.example.com/{repo}/issues/{number}`
+    pub issue_url: Option<String>,
+    /// 例如 `https://example.com/{user}`
+    pub mention_url: Option<String>,
+    /// 例如 `https://example.com/{repo}/commit/{sha}`
+    pub commit_url: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Reference<'a> {
+    Issue {
+        repo: Option<&'a str>,
+        number: &'a str,
+    },
+    Mention(&'a str),
+    Commit(&'a str),
+}
+
+impl Reference<'_> {
+    pub fn url(&self, options: &AutolinkOptions) -> Option<String> {
+        let default_repo = options.repo.as_deref().unwrap_or("");
+        match self {
+            Self::Issue { repo, number } => {
+                let url = options.issue_url.as_ref()?;
+                Some(url.replace("{repo}", repo.unwrap_or(default_repo)).replace("{number}", number))
+            }
+            Self::Mention(user) => Some(options.mention_url.as_ref()?.replace("{user}", user)),
+            Self::Commit(sha) => Some(
+                options
+                    .commit_url
+                    .as_ref()?
+                    .replace("{repo}", default_repo)
+                    .replace("{sha}", sha),
+            ),
+        }
+    }
+
+    /// 链接文字，提交只显示前 7 位
+    pub fn text(&self) -> String {
+        match self {
+            Self::Issue { repo: Some(repo), number } => format!("{}#{}", repo, number),
+            Self::Issue { repo: None, number } => format!("#{}", number),
+            Self::Mention(user) => format!("@{}", user),
+            Self::Commit(sha) => sha[..7].to_string(),
+        }
+    }
+}
+
+/// 在 `i` 处识别引用，`org/repo#123` 会向前找仓库名，但不早于 `text_start`。
+/// 返回引用的起止位置
+pub fn scan<'a>(
+    line: &'a str,
+    i: usize,
+    text_start: usize,
+    options: &AutolinkOptions,
+) -> Option<(usize, Reference<'a>, usize)> {
+    let bytes = line.as_bytes();
+    let byte = bytes[i];
+    let prev_is_word = is_word(units::char_before(line, i));
+    if byte == b'#' && options.issue_url.is_some() {
+        let digits = count(&bytes[i + 1..], |b| b.is_ascii_digit());
+        let end = i + 1 + digits;
+        if digits == 0 || is_word(units::char_at(line, end)) {
+            return None;
+        }
+        let number = &line[i + 1..end];
+        let repo_len = bytes[text_start..i].iter().rev().take_while(|b| is_repo_byte(**b)).count();
+        let start = i - repo_len;
+        let repo = &line[start..i];
+        let slashes = repo.matches('/').count();
+        if repo_len > 0 && slashes == 1 && !repo.starts_with('/') && !repo.ends_with('/') {
+            return Some((start, Reference::Issue { repo: Some(repo), number }, end));
+        }
+        if repo_len > 0 || prev_is_word {
+            return None;
+        }
+        return Some((i, Reference::Issue { repo: None, number }, end));
+    }
+    // 前面是字母数字时可能是邮箱地址
+    if prev_is_word || i.checked_sub(1).is_some_and(|p| bytes[p] == b'.') {
+        return None;
+    }
+    if byte == b'@' && options.mention_url.is_some() {
+        let len = count(&bytes[i + 1..], |b| b.is_ascii_alphanumeric() || b == b'-');
+        let end = i + 1 + len;
+        if len == 0 || len > 39 || bytes[i + 1] == b'-' || is_word(units::char_at(line, end)) {
+            return None;
+        }
+        return Some((i, Reference::Mention(&line[i + 1..end]), end));
+    }
+    if options.commit_url.is_some() && byte.is_ascii_hexdigit() {
+        let len = count(&bytes[i..], |b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+        let end = i + len;
+        let sha = &line[i..end];
+        // 同时有数字和字母，避免把普通的数字或 `deadbeef` 这类单词当成 SHA
+        let digit = sha.bytes().any(|b| b.is_ascii_digit());
+        let letter = sha.bytes().any(|b| b.is_ascii_alphabetic());
+        if (7..=40).contains(&len) && digit && letter && !is_word(units::char_at(line, end)) {
+            return Some((i, Reference::Commit(sha), end));
+        }
+    }
+    None
+}
+
+fn count(bytes: &[u8], accept: impl Fn(u8) -> bool) -> usize {
+    bytes.iter().take_while(|b| accept(**b)).count()
+}
+
+fn is_word(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn is_repo_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, parser::{tests::render_with, ParserImpl}};
+
+    #[test]
+    fn references_and_boundaries() {
+        let options = AutolinkOptions {
+            repo: Some("team/app".into()),
+            issue_url: Some("/{repo}/issues/{number}".into()),
+            mention_url: Some("/u/{user}".into()),
+            commit_url: Some("/{repo}/commit/{sha}".into()),
+        };
+        let find = |src: &str| -> Vec<(String, Option<String>)> {
+            let mut found = Vec::new();
+            let mut i = 0;
+            while i < src.len() {
+                match scan(src, i, 0, &options) {
+                    Some((_, r, end)) => {
+                        found.push((r.text(), r.url(&options)));
+                        i = end;
+                    }
+                    None => i = units::next_char(src, i),
+                }
+            }
+            found
+        };
+        assert_eq!(
+            find("fix #12, org/lib#7 by @ann-b in a1b2c3d4e5"),
+            vec![
+                ("#12".into(), Some("/team/app/issues/12".into())),
+                ("org/lib#7".into(), Some("/org/lib/issues/7".into())),
+                ("@ann-b".into(), Some("/u/ann-b".into())),
+                ("a1b2c3d".into(), Some("/team/app/commit/a1b2c3d4e5".into())),
+            ]
+        );
+        assert!(find("a@b.com x#1 #1a 1234567 deadbeef abc123").is_empty());
+    }
+
+    #[test]
+    fn release_notes_autolinks() {
+        let options = Options {
+            autolink: Some(AutolinkOptions {
+                repo: Some("team/app".to_string()),
+                issue_url: Some("https://bugs.local/{repo}/{number}".to_string()),
+                mention_url: Some("https://people.local/{user}".to_string()),
+                commit_url: None,
+            }),
+            ..Options::default()
+        };
+        let src = "#123 fixed by @amy\n`#1` [see #2](/x) 0a1b2c3d\n#";
+        assert_eq!(
+            render_with(ParserImpl::new(src.as_bytes()), &options),
+            "<p><a href=\"https://bugs.local/team/app/123\">#123</a> fixed by <a href=\"https://people.local/amy\">@amy</a></p>\
+             <p><code>#1</code> <a href=\"/x\">see #2</a> 0a1b2c3d</p><p>#</p>"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! 块级语法的识别，只判断一行是否为某种块的开始/结束，输出由 `Parser` 负责。
+use crate::{
+    attributes::{self, Attributes},
+    options::Options, parser::{Fence, MathDelimiter}, units,
+};
+
+/// 其中的行（去掉前缀后）继续按普通块解析的容器
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Container {
+    // > [!NOTE]
+    Alert,
+    // !!! note "Title"
+    Admonition,
+    // :::name，记录冒号个数
+    Directive(usize),
+}
+
+/// ```` ```rust {#id .numberLines} ```` 或 ```` ``` {.rust} ````，
+/// 没有写语言时取属性中的第一个 class 作为语言
+pub fn fence_open(line: &str) -> Option<(Fence, Option<String>, Attributes)> {
+    let trimmed = units::trim_start(line);
+    let marker = match trimmed.bytes().next() {
+        Some(b'`') => b'`',
+        Some(b'~') => b'~',
+        _ => return None,
+    };
+    let len = units::count_leading(trimmed, marker);
+    if len < 3 {
+        return None;
+    }
+    let info = units::trim(&trimmed[len..]);
+    if marker == b'`' && info.contains('`') {
+        return None;
+    }
+    let (info, mut attrs) = attributes::trailing(info).unwrap_or((info, Attributes::default()));
+    let mut lang = info
+        .split([' ', '\t'])
+        .next()
+        .filter(|lang| !lang.is_empty())
+        .map(String::from);
+    if lang.is_none() && !attrs.classes.is_empty() {
+        lang = Some(attrs.classes.remove(0));
+    }
+    Some((Fence { marker, len, math: false }, lang, attrs))
+}
+
+pub fn fence_close(line: &str, fence: Fence) -> bool {
+    let trimmed = units::trim(line);
+    let len = units::count_leading(trimmed, fence.marker);
+    len >= fence.len && len == trimmed.len()
+}
+
+/// `$$`/`\[` 开头的公式块
+pub enum MathOpen<'a> {
+    /// 同一行闭合，其中为公式
+    Closed(&'a str),
+    /// 多行公式块的第一行，其中为起始符号之后的内容
+    Open(&'a str),
+}
+
+/// 公式块的第一行；同一行闭合后还有其它文本时是段落中的行内公式，返回 `None`
+pub fn math_open(line: &str) -> Option<(MathDelimiter, MathOpen<'_>)> {
+    let trimmed = units::trim(line);
+    let delimiter = if trimmed.starts_with("$$") {
+        MathDelimiter::Dollar
+    } else if trimmed.starts_with("\\[") {
+        MathDelimiter::Bracket
+    } else {
+        return None;
+    };
+    let rest = &trimmed[2..];
+    match units::find(rest, delimiter.close(), 0) {
+        Some(end) if end + 2 == rest.len() => Some((delimiter, MathOpen::Closed(&rest[..end]))),
+        Some(_) => None,
+        None => Some((delimiter, MathOpen::Open(rest))),
+    }
+}
+
+/// 多行公式块的最后一行，返回结束符号之前的内容
+pub fn math_close(line: &str, delimiter: MathDelimiter) -> Option<&str> {
+    units::trim_end(line).strip_suffix(delimiter.close())
+}
+
+/// 独占一行的 `{#id .class}`，作用于下一个块
+pub fn attribute_line(line: &str) -> Option<Attributes> {
+    let trimmed = units::trim(line);
+    let (attrs, len) = attributes::parse(trimmed)?;
+    if len == trimmed.len() {
+        Some(attrs)
+    } else {
+        None
+    }
+}
+
+/// `## title`，容器内的行不经过 `Tokenizer` 的行首判断，在这里补上
+pub fn header_mark(line: &str) -> Option<(u8, &str)> {
+    let level = units::count_leading(line, b'#');
+    if (1..=6).contains(&level) && line.as_bytes().get(level) == Some(&b' ') {
+        Some((level as u8, &line[level + 1..]))
+    } else {
+        None
+    }
+}
+
+/// `> [!NOTE]`，返回配置中对应的类型名
+pub fn alert_open(line: &str, options: &Options) -> Option<String> {
+    let marker = units::trim_start(units::trim(line).strip_prefix('>')?);
+    find_kind(marker.strip_prefix("[!")?.strip_suffix(']')?, options)
+}
+
+/// `!!! note "Title"`，没有写标题时返回 `None`，`""` 返回空标题
+pub fn admonition_open<'a>(line: &'a str, options: &Options) -> Option<(String, Option<&'a str>)> {
+    let rest = units::trim(line.strip_prefix("!!! ")?);
+    let kind_len = rest.find([' ', '\t']).unwrap_or(rest.len());
+    let kind = find_kind(&rest[..kind_len], options)?;
+    let title = units::trim(&rest[kind_len..]);
+    if title.is_empty() {
+        return Some((kind, None));
+    }
+    if title.len() >= 2 && title.starts_with('"') && title.ends_with('"') {
+        return Some((kind, Some(&title[1..title.len() - 1])));
+    }
+    None
+}
+
+/// 容器中的一行去掉前缀后的内容，`None` 表示容器在这一行之前结束
+pub fn container_content(container: Container, line: &str) -> Option<&str> {
+    match container {
+        Container::Alert => {
+            let content = units::trim_start(line).strip_prefix('>')?;
+            Some(content.strip_prefix(' ').unwrap_or(content))
+        }
+        Container::Admonition => {
+            if units::is_blank(line) {
+                Some("")
+            } else {
+                line.strip_prefix("    ").or_else(|| line.strip_prefix('\t'))
+            }
+        }
+        Container::Directive(_) => Some(line),
+    }
+}
+
+pub fn default_title(kind: &str) -> String {
+    let lower = kind.to_lowercase();
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn find_kind(kind: &str, options: &Options) -> Option<String> {
+    options
+        .alert_kinds
+        .iter()
+        .find(|k| !kind.is_empty() && k.eq_ignore_ascii_case(kind))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{tests::render_with, ParserImpl};
+
+    fn render(src: &str) -> String {
+        render_with(ParserImpl::new(src.as_bytes()), &Options::default())
+    }
+
+    #[test]
+    fn alerts_and_admonitions() {
+        assert_eq!(
+            render("> [!warning]\n> ## Careful\n> body\ntext\n"),
+            "<div class=\"markdown-alert markdown-alert-warning\">\
+             <p class=\"markdown-alert-title\">Warning</p><h2>Careful</h2><br><p>body</p></div><p>text</p>"
+        );
+        assert_eq!(
+            render("!!! tip \"Read <me>\"\n    one\n\n    two\n\nafter"),
+            "<div class=\"markdown-alert markdown-alert-tip\">\
+             <p class=\"markdown-alert-title\">Read &lt;me&gt;</p><p>one</p><br><p>two</p></div><br><p>after</p>"
+        );
+        // 不在配置中的类型按普通文本处理
+        assert_eq!(render("!!! danger\n"), "<p>!!! danger</p>");
+    }
+}
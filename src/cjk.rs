@@ -0,0 +1,93 @@
+//! 中日韩文字的判断以及相关的排版规则：软换行、强调的边界和盘古之白。
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CjkOptions {
+    /// 在中日韩文字和拉丁字母、数字之间插入空格
+    pub pangu: bool,
+}
+
+/// 汉字、假名、谚文以及中日韩标点和全角字符
+pub fn is_cjk(c: char) -> bool {
+    is_cjk_letter(c) || is_cjk_punctuation(c)
+}
+
+pub fn is_cjk_letter(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11FF}'     // 谚文字母
+        | '\u{2E80}'..='\u{2FDF}'   // 部首
+        | '\u{3040}'..='\u{30FF}'   // 平假名、片假名
+        | '\u{3100}'..='\u{312F}'   // 注音符号
+        | '\u{3130}'..='\u{318F}'   // 谚文兼容字母
+        | '\u{31F0}'..='\u{31FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}'   // 谚文音节
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2FA1F}'
+    )
+}
+
+pub fn is_cjk_punctuation(c: char) -> bool {
+    matches!(c,
+        '\u{3000}'..='\u{303F}'     // 中日韩符号和标点
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF00}'..='\u{FFEF}'   // 全角字符
+        | '“' | '”' | '‘' | '’' | '…' | '—' | '·'
+    )
+}
+
+/// 段落中相邻两行之间的软换行：两侧都是中日韩文字时不输出
+pub fn soft_break(prev: Option<char>, next: Option<char>) -> bool {
+    !matches!((prev, next), (Some(p), Some(n)) if is_cjk(p) && is_cjk(n))
+}
+
+/// 盘古之白：中日韩文字与拉丁字母、数字相邻时中间加空格
+pub fn pangu(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 4);
+    let mut prev = None;
+    for c in text.chars() {
+        if prev.is_some_and(|prev| pangu_between(prev, c)) {
+            out.push(' ');
+        }
+        out.push(c);
+        prev = Some(c);
+    }
+    out
+}
+
+/// 相邻的两个字符之间是否要加盘古之白
+pub fn pangu_between(prev: char, next: char) -> bool {
+    let latin = |c: char| c.is_ascii_alphanumeric();
+    (is_cjk_letter(prev) && latin(next)) || (latin(prev) && is_cjk_letter(next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, parser::{tests::render_with, ParserImpl}};
+
+    #[test]
+    fn script_data_and_pangu() {
+        assert!(is_cjk('中') && is_cjk('か') && is_cjk('한') && is_cjk('「'));
+        assert!(!is_cjk('a') && !is_cjk('é'));
+        assert_eq!(pangu("使用Rust编写，支持2种模式。"), "使用 Rust 编写，支持 2 种模式。");
+        assert!(!soft_break(Some('文'), Some('字')));
+        assert!(soft_break(Some('文'), Some('x')));
+    }
+
+    #[test]
+    fn soft_breaks_between_cjk_lines() {
+        let options = Options {
+            soft_breaks: true,
+            cjk: Some(Default::default()),
+            ..Options::default()
+        };
+        let src = "段落的第一行\n  第二行 and\nthird line\n\n# 标题\nnext\n> [!NOTE]\n> 提示\n> 内容\n";
+        assert_eq!(
+            render_with(ParserImpl::new(src.as_bytes()), &options),
+            "<p>段落的第一行第二行 and\nthird line</p><h1>标题</h1><br><p>next</p>\
+             <div class=\"markdown-alert markdown-alert-note\"><p class=\"markdown-alert-title\">Note</p>\
+             <p>提示内容</p></div>"
+        );
+    }
+}
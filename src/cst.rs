@@ -0,0 +1,323 @@
+//! 无损语法树：保留输入的每一个字节（标记、空白、`CR`/`LF`/`CRLF` 换行），
+//! 修改其中一部分后用 `to_markdown` 写回，没有改动的地方与原文逐字节相同。
+//!
+//! 只按行划分块并拆出每行的前缀和标记，不做行内解析，适合机器人修改更新日志、
+//! 勾选任务列表这类只动局部的场景。标题、代码块、公式块和提示框按解析器的 `block` 规则识别，
+//! 行的切分与扫描文档时相同。
+use std::{fmt, io::{Read, Result as IoResult}};
+
+use crate::{
+    block::{self, Container, MathOpen},
+    parser::{Fence, MathDelimiter},
+    units,
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyntaxTree {
+    pub blocks: Vec<SyntaxBlock>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxBlock {
+    pub kind: SyntaxKind,
+    pub lines: Vec<SyntaxLine>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+    /// 连续的空行
+    Blank,
+    Heading(u8),
+    Paragraph,
+    /// `-`、`*`、`+` 或 `1.` 开头的一项，以及其后缩进的行
+    ListItem,
+    /// `>` 开头的连续行，包括 `> [!NOTE]` 提示框
+    Quote,
+    /// ```` ``` ```` 或 `~~~` 代码块，包括起止行
+    CodeBlock,
+    /// `$$` 公式块
+    MathBlock,
+}
+
+/// 一行拆成 `prefix`、`marker`、`content` 和换行符，依次拼接就是原文
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyntaxLine {
+    /// 行首的空白和 `>` 引用符号
+    pub prefix: String,
+    /// 标题的 `## `、列表的 `- ` 以及任务框 `[ ] `
+    pub marker: String,
+    pub content: String,
+    pub ending: LineEnding,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// 文件最后一行没有换行符
+    #[default]
+    None,
+    Lf,
+    Cr,
+    CrLf,
+}
+
+impl LineEnding {
+    fn from_str(ending: &str) -> Self {
+        match ending {
+            "\n" => Self::Lf,
+            "\r" => Self::Cr,
+            "\r\n" => Self::CrLf,
+            _ => Self::None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::None => "",
+            Self::Lf => "\n",
+            Self::Cr => "\r",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+impl SyntaxTree {
+    pub fn parse<R: Read>(mut reader: R) -> IoResult<Self> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        Ok(Self::from_text(&text))
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        let mut blocks: Vec<SyntaxBlock> = Vec::new();
+        // 正在收集的代码块或公式块
+        let mut open = None;
+        for (line, ending) in units::lines(text) {
+            let ending = LineEnding::from_str(ending);
+            if let Some(fence) = open {
+                let last = blocks.last_mut().expect("open block");
+                last.lines.push(SyntaxLine::raw(line, ending));
+                let closed = match fence {
+                    Open::Fence(fence) => block::fence_close(line, fence),
+                    Open::Math(delimiter) => block::math_close(line, delimiter).is_some(),
+                };
+                if closed {
+                    open = None;
+                }
+                continue;
+            }
+            let (kind, opened) = classify(line);
+            open = opened;
+            let continues = blocks.last().is_some_and(|last| match (last.kind, kind) {
+                (SyntaxKind::Blank, SyntaxKind::Blank) => true,
+                (SyntaxKind::Quote, SyntaxKind::Quote) => true,
+                (SyntaxKind::Paragraph, SyntaxKind::Paragraph) => true,
+                // 列表项中缩进的行
+                (SyntaxKind::ListItem, SyntaxKind::Paragraph) => line.starts_with([' ', '\t']),
+                _ => false,
+            });
+            let line = match kind {
+                SyntaxKind::CodeBlock | SyntaxKind::MathBlock => SyntaxLine::raw(line, ending),
+                _ => SyntaxLine::split(line, ending),
+            };
+            match blocks.last_mut() {
+                Some(last) if continues => last.lines.push(line),
+                _ => blocks.push(SyntaxBlock { kind, lines: vec![line] }),
+            }
+        }
+        Self { blocks }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        self.to_string()
+    }
+
+    /// 所有带任务框的行
+    pub fn tasks_mut(&mut self) -> impl Iterator<Item = &mut SyntaxLine> {
+        self.blocks
+            .iter_mut()
+            .filter(|b| b.kind == SyntaxKind::ListItem || b.kind == SyntaxKind::Quote)
+            .flat_map(|b| b.lines.iter_mut())
+            .filter(|line| line.task().is_some())
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.blocks.iter().try_for_each(|block| write!(f, "{}", block))
+    }
+}
+
+impl SyntaxBlock {
+    /// 块的原文
+    pub fn text(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for SyntaxBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines.iter().try_for_each(|line| write!(f, "{}", line))
+    }
+}
+
+impl SyntaxLine {
+    // 代码块中的行，不拆分
+    fn raw(content: &str, ending: LineEnding) -> Self {
+        Self {
+            content: content.to_string(),
+            ending,
+            ..Self::default()
+        }
+    }
+
+    fn split(text: &str, ending: LineEnding) -> Self {
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        // 行首空白以及一层或多层 `> `
+        loop {
+            while bytes.get(i).is_some_and(|b| *b == b' ' || *b == b'\t') {
+                i += 1;
+            }
+            if bytes.get(i) != Some(&b'>') {
+                break;
+            }
+            i += 1;
+            if bytes.get(i) == Some(&b' ') {
+                i += 1;
+            }
+        }
+        let prefix = i;
+        i += marker_len(&bytes[i..]);
+        Self {
+            prefix: text[..prefix].to_string(),
+            marker: text[prefix..i].to_string(),
+            content: text[i..].to_string(),
+            ending,
+        }
+    }
+
+    /// 任务框是否勾选，不是任务项时返回 `None`
+    pub fn task(&self) -> Option<bool> {
+        let marker = self.marker.trim_end();
+        if marker.ends_with("[ ]") {
+            Some(false)
+        } else if marker.ends_with("[x]") || marker.ends_with("[X]") {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    /// 勾选或取消勾选任务框，只改动框中的一个字符
+    pub fn set_task(&mut self, checked: bool) -> bool {
+        let Some(close) = self.marker.rfind(']').filter(|_| self.task().is_some()) else {
+            return false;
+        };
+        self.marker.replace_range(close - 1..close, if checked { "x" } else { " " });
+        true
+    }
+}
+
+impl fmt::Display for SyntaxLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}{}", self.prefix, self.marker, self.content, self.ending.as_str())
+    }
+}
+
+// 尚未闭合的代码块或公式块
+#[derive(Clone, Copy)]
+enum Open {
+    Fence(Fence),
+    Math(MathDelimiter),
+}
+
+// 一行开始的块，与解析器使用同样的规则识别标题、代码块、公式块和提示框
+fn classify(line: &str) -> (SyntaxKind, Option<Open>) {
+    if units::is_blank(line) {
+        return (SyntaxKind::Blank, None);
+    }
+    if let Some((level, _)) = block::header_mark(line) {
+        return (SyntaxKind::Heading(level), None);
+    }
+    if let Some((fence, _, _)) = block::fence_open(line) {
+        return (SyntaxKind::CodeBlock, Some(Open::Fence(fence)));
+    }
+    match block::math_open(line) {
+        Some((_, MathOpen::Closed(_))) => return (SyntaxKind::MathBlock, None),
+        Some((delimiter, MathOpen::Open(_))) => return (SyntaxKind::MathBlock, Some(Open::Math(delimiter))),
+        None => {}
+    }
+    if block::container_content(Container::Alert, line).is_some() {
+        (SyntaxKind::Quote, None)
+    } else if marker_len(units::trim_start(line).as_bytes()) > 0 {
+        (SyntaxKind::ListItem, None)
+    } else {
+        (SyntaxKind::Paragraph, None)
+    }
+}
+
+// 行首标记的长度：`## `，或者列表符号加上可选的任务框
+fn marker_len(bytes: &[u8]) -> usize {
+    let hashes = bytes.iter().take_while(|b| **b == b'#').count();
+    if (1..=6).contains(&hashes) && bytes.get(hashes) == Some(&b' ') {
+        return hashes + 1;
+    }
+    let digits = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    let bullet = match bytes.first() {
+        Some(b'-' | b'*' | b'+') => 1,
+        _ if (1..=9).contains(&digits) && matches!(bytes.get(digits), Some(b'.' | b')')) => digits + 1,
+        _ => return 0,
+    };
+    if bytes.get(bullet) != Some(&b' ') {
+        return 0;
+    }
+    let len = bullet + 1;
+    match bytes.get(len..len + 4) {
+        Some(b"[ ] " | b"[x] " | b"[X] ") => len + 4,
+        _ => len,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_and_edit_tasks() {
+        let src = "#  Changelog \r\n\r\n## 1.0\r\n- [ ] 发布\n  说明\t\n* [x] 测试\r\n> [!NOTE]\n>   注意\n\n\
+                   ```rust\n- [ ] 不是任务\n## 不是标题\n```\n$$\n# x\n$$\n1. 第一\n末行无换行 ";
+        let mut tree = SyntaxTree::parse(src.as_bytes()).unwrap();
+        assert_eq!(tree.to_markdown(), src);
+
+        let kinds: Vec<SyntaxKind> = tree.blocks.iter().map(|b| b.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                SyntaxKind::Heading(1),
+                SyntaxKind::Blank,
+                SyntaxKind::Heading(2),
+                SyntaxKind::ListItem,
+                SyntaxKind::ListItem,
+                SyntaxKind::Quote,
+                SyntaxKind::Blank,
+                SyntaxKind::CodeBlock,
+                SyntaxKind::MathBlock,
+                SyntaxKind::ListItem,
+                SyntaxKind::Paragraph,
+            ]
+        );
+        assert_eq!(tree.blocks[0].lines[0].content, " Changelog ");
+
+        for task in tree.tasks_mut() {
+            let checked = task.task() == Some(true);
+            task.set_task(!checked);
+        }
+        tree.blocks[2].lines[0].content = "1.1".to_string();
+        assert_eq!(
+            tree.to_markdown(),
+            src.replacen("## 1.0", "## 1.1", 1)
+                .replacen("- [ ] 发布", "- [x] 发布", 1)
+                .replacen("* [x]", "* [ ]", 1)
+        );
+    }
+}
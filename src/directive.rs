@@ -0,0 +1,189 @@
+//! 通用指令语法：`:name[label]{attrs}`（行内）、`::name[label]{attrs}`（独占一行）
+//! 以及 `:::name` ... `:::` 容器。只有注册过处理函数的名字才会被识别，
+//! 其它情况原样按文本输出。
+use std::{collections::HashMap, fmt, io::Result as IoResult};
+
+use crate::{
+    attributes::{self, Attributes},
+    units,
+    writer::Writer,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DirectiveKind {
+    /// `:name[label]{attrs}`
+    Text,
+    /// `::name[label]{attrs}`
+    Leaf,
+    /// `:::name[label]{attrs}` ... `:::`
+    Container,
+}
+
+/// 交给处理函数的指令
+#[derive(Debug)]
+pub struct Directive<'a> {
+    pub kind: DirectiveKind,
+    pub name: &'a str,
+    /// `[...]` 中的原始文本
+    pub label: Option<&'a str>,
+    pub attributes: &'a Attributes,
+    /// 已经渲染好的子内容：行内和独占一行的指令为 label，容器为其中的所有块
+    pub children: &'a [u8],
+}
+
+pub trait DirectiveHandler {
+    fn handle(&self, directive: &Directive, out: &mut dyn Writer) -> IoResult<()>;
+}
+
+impl<F> DirectiveHandler for F
+where
+    F: Fn(&Directive, &mut dyn Writer) -> IoResult<()>,
+{
+    fn handle(&self, directive: &Directive, out: &mut dyn Writer) -> IoResult<()> {
+        self(directive, out)
+    }
+}
+
+/// 指令名到处理函数的映射
+#[derive(Default)]
+pub struct DirectiveRegistry {
+    handlers: HashMap<String, Box<dyn DirectiveHandler>>,
+}
+
+impl DirectiveRegistry {
+    pub fn register<H: DirectiveHandler + 'static>(&mut self, name: impl Into<String>, handler: H) {
+        self.handlers.insert(name.into(), Box::new(handler));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn DirectiveHandler> {
+        self.handlers.get(name).map(|h| h.as_ref())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+impl fmt::Debug for DirectiveRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+/// `name[label]{attrs}` 部分
+#[derive(Debug, PartialEq, Eq)]
+pub struct DirectiveSpec<'a> {
+    pub name: String,
+    pub label: Option<&'a str>,
+    pub attributes: Attributes,
+}
+
+impl DirectiveSpec<'_> {
+    /// label 在 `name[label]{attrs}` 中开始的下标
+    pub fn label_offset(&self) -> usize {
+        self.name.len() + 1
+    }
+}
+
+/// 解析 `name[label]{attrs}`，返回消耗的长度；`require_suffix` 为真时
+/// `[label]` 和 `{attrs}` 至少要有一个，用于行内指令避免误识别 `a:b` 之类的文本
+pub fn parse_spec(text: &str, require_suffix: bool) -> Option<(DirectiveSpec<'_>, usize)> {
+    let bytes = text.as_bytes();
+    let name_len = bytes
+        .iter()
+        .take_while(|b| b.is_ascii_alphanumeric() || **b == b'-' || **b == b'_')
+        .count();
+    if name_len == 0 || !bytes[0].is_ascii_alphabetic() {
+        return None;
+    }
+    let mut i = name_len;
+    let mut label = None;
+    if bytes.get(i) == Some(&b'[') {
+        let close = units::bracket_end(text, i)?;
+        label = Some(&text[i + 1..close]);
+        i = close + 1;
+    }
+    let mut attributes = Attributes::default();
+    if bytes.get(i) == Some(&b'{') {
+        let (attrs, len) = attributes::parse(&text[i..])?;
+        attributes = attrs;
+        i += len;
+    }
+    if require_suffix && i == name_len {
+        return None;
+    }
+    let spec = DirectiveSpec {
+        name: text[..name_len].to_string(),
+        label,
+        attributes,
+    };
+    Some((spec, i))
+}
+
+/// `::name[label]{attrs}` 独占一行
+pub fn leaf_open<'a>(line: &'a str, registry: &DirectiveRegistry) -> Option<DirectiveSpec<'a>> {
+    let trimmed = units::trim(line);
+    if units::count_leading(trimmed, b':') != 2 {
+        return None;
+    }
+    let (spec, len) = parse_spec(&trimmed[2..], false)?;
+    if len + 2 != trimmed.len() || registry.get(&spec.name).is_none() {
+        return None;
+    }
+    Some(spec)
+}
+
+/// `:::name[label]{attrs}`，返回冒号的个数，结束行至少要有同样多的冒号
+pub fn container_open<'a>(
+    line: &'a str,
+    registry: &DirectiveRegistry,
+) -> Option<(DirectiveSpec<'a>, usize)> {
+    let trimmed = units::trim(line);
+    let colons = units::count_leading(trimmed, b':');
+    if colons < 3 {
+        return None;
+    }
+    let rest = units::trim_start(&trimmed[colons..]);
+    let (spec, len) = parse_spec(rest, false)?;
+    if len != rest.len() || registry.get(&spec.name).is_none() {
+        return None;
+    }
+    Some((spec, colons))
+}
+
+pub fn container_close(line: &str, colons: usize) -> bool {
+    let trimmed = units::trim(line);
+    let len = units::count_leading(trimmed, b':');
+    len >= colons && len == trimmed.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, parser::{tests::render_with, ParserImpl}};
+
+    #[test]
+    fn directives_call_registered_handlers() {
+        let mut options = Options::default();
+        options.directives.register("badge", |d: &Directive, out: &mut dyn Writer| {
+            out.write(format!("<b{}>", d.attributes.to_html()).as_bytes())?;
+            out.write(d.children)?;
+            out.write(b"</b>").map(|_| ())
+        });
+        options.directives.register("card", |d: &Directive, out: &mut dyn Writer| {
+            out.write(format!("<section data-kind=\"{:?}\" title=\"{}\">", d.kind, d.label.unwrap_or("")).as_bytes())?;
+            out.write(d.children)?;
+            out.write(b"</section>").map(|_| ())
+        });
+
+        let src = "a :badge[new]{.hot} b:badge[x] :other[y]\n::card[Leaf]\n:::card[Box]{#c}\n> [!NOTE]\n> hi\n:::\nend";
+        assert_eq!(
+            render_with(ParserImpl::new(src.as_bytes()), &options),
+            "<p>a <b class=\"hot\">new</b> b:badge[x] :other[y]</p>\
+             <section data-kind=\"Leaf\" title=\"Leaf\">Leaf</section>\
+             <section data-kind=\"Container\" title=\"Box\">\
+             <div class=\"markdown-alert markdown-alert-note\"><p class=\"markdown-alert-title\">Note</p><p>hi</p></div>\
+             </section><p>end</p>"
+        );
+    }
+}
@@ -0,0 +1,40 @@
+//! 需要看完整篇文档才能确定的信息，例如缩写的定义可以出现在任何位置。
+//! 开启这类功能时 `ParserImpl` 先读入全文扫描一遍，再按正常流程输出。
+use crate::{
+    abbr::{self, Abbreviation},
+    block,
+    options::Options,
+    units,
+};
+
+#[derive(Debug, Default)]
+pub struct DocumentInfo {
+    pub abbreviations: Vec<Abbreviation>,
+}
+
+pub fn scan(text: &str, options: &Options) -> DocumentInfo {
+    let mut info = DocumentInfo::default();
+    let mut fence = None;
+    for (line, _) in units::lines(text) {
+        // 代码块中的内容不算定义
+        if let Some(open) = fence {
+            if block::fence_close(line, open) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some((open, _, _)) = block::fence_open(line) {
+            fence = Some(open);
+            continue;
+        }
+        if options.abbreviations {
+            // 按原始行判断：提示框中的行带有 `>` 或缩进，与解析时一样不算定义
+            if let Some(def) = abbr::parse_definition(line) {
+                // 重复定义时后面的生效
+                info.abbreviations.retain(|a| a.abbr != def.abbr);
+                info.abbreviations.push(def);
+            }
+        }
+    }
+    info
+}
@@ -0,0 +1,436 @@
+//! `:smile:` 形式的 emoji 短代码，名字与 GitHub 一致。
+
+/// 短代码和对应的 emoji，按名字排序以便二分查找
+pub static SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("1234", "🔢"),
+    ("8ball", "🎱"),
+    ("a", "🅰️"),
+    ("ab", "🆎"),
+    ("abc", "🔤"),
+    ("airplane", "✈️"),
+    ("alarm_clock", "⏰"),
+    ("alien", "👽"),
+    ("ambulance", "🚑"),
+    ("anchor", "⚓"),
+    ("angel", "👼"),
+    ("anger", "💢"),
+    ("angry", "😠"),
+    ("anguished", "😧"),
+    ("ant", "🐜"),
+    ("apple", "🍎"),
+    ("arrow_down", "⬇️"),
+    ("arrow_left", "⬅️"),
+    ("arrow_right", "➡️"),
+    ("arrow_up", "⬆️"),
+    ("art", "🎨"),
+    ("astonished", "😲"),
+    ("atom_symbol", "⚛️"),
+    ("b", "🅱️"),
+    ("baby", "👶"),
+    ("balloon", "🎈"),
+    ("bamboo", "🎍"),
+    ("banana", "🍌"),
+    ("bangbang", "‼️"),
+    ("bank", "🏦"),
+    ("bar_chart", "📊"),
+    ("baseball", "⚾"),
+    ("basketball", "🏀"),
+    ("bath", "🛀"),
+    ("battery", "🔋"),
+    ("bear", "🐻"),
+    ("bee", "🐝"),
+    ("beer", "🍺"),
+    ("beers", "🍻"),
+    ("beetle", "🐞"),
+    ("beginner", "🔰"),
+    ("bell", "🔔"),
+    ("bike", "🚲"),
+    ("bird", "🐦"),
+    ("birthday", "🎂"),
+    ("blue_heart", "💙"),
+    ("blush", "😊"),
+    ("bomb", "💣"),
+    ("book", "📖"),
+    ("bookmark", "🔖"),
+    ("books", "📚"),
+    ("boom", "💥"),
+    ("bow", "🙇"),
+    ("brain", "🧠"),
+    ("bread", "🍞"),
+    ("broken_heart", "💔"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("bus", "🚌"),
+    ("cake", "🍰"),
+    ("calendar", "📆"),
+    ("camera", "📷"),
+    ("car", "🚗"),
+    ("cat", "🐱"),
+    ("cd", "💿"),
+    ("chart_with_downwards_trend", "📉"),
+    ("chart_with_upwards_trend", "📈"),
+    ("checkered_flag", "🏁"),
+    ("cherries", "🍒"),
+    ("chicken", "🐔"),
+    ("christmas_tree", "🎄"),
+    ("clap", "👏"),
+    ("clipboard", "📋"),
+    ("clock1", "🕐"),
+    ("closed_book", "📕"),
+    ("cloud", "☁️"),
+    ("clown_face", "🤡"),
+    ("coffee", "☕"),
+    ("computer", "💻"),
+    ("confetti_ball", "🎊"),
+    ("confused", "😕"),
+    ("construction", "🚧"),
+    ("cookie", "🍪"),
+    ("cool", "🆒"),
+    ("copyright", "©️"),
+    ("cow", "🐮"),
+    ("crab", "🦀"),
+    ("crossed_fingers", "🤞"),
+    ("crown", "👑"),
+    ("cry", "😢"),
+    ("crystal_ball", "🔮"),
+    ("cupid", "💘"),
+    ("dart", "🎯"),
+    ("dash", "💨"),
+    ("date", "📅"),
+    ("disappointed", "😞"),
+    ("dizzy", "💫"),
+    ("dog", "🐶"),
+    ("dollar", "💵"),
+    ("dolphin", "🐬"),
+    ("door", "🚪"),
+    ("dragon", "🐉"),
+    ("dress", "👗"),
+    ("droplet", "💧"),
+    ("ear", "👂"),
+    ("earth_asia", "🌏"),
+    ("egg", "🥚"),
+    ("eggplant", "🍆"),
+    ("email", "📧"),
+    ("envelope", "✉️"),
+    ("exclamation", "❗"),
+    ("expressionless", "😑"),
+    ("eyes", "👀"),
+    ("facepunch", "👊"),
+    ("fearful", "😨"),
+    ("file_folder", "📁"),
+    ("fire", "🔥"),
+    ("fireworks", "🎆"),
+    ("fish", "🐟"),
+    ("fist", "✊"),
+    ("flushed", "😳"),
+    ("fox_face", "🦊"),
+    ("frog", "🐸"),
+    ("frowning", "😦"),
+    ("gear", "⚙️"),
+    ("gem", "💎"),
+    ("ghost", "👻"),
+    ("gift", "🎁"),
+    ("globe_with_meridians", "🌐"),
+    ("grapes", "🍇"),
+    ("green_heart", "💚"),
+    ("grimacing", "😬"),
+    ("grin", "😁"),
+    ("grinning", "😀"),
+    ("guitar", "🎸"),
+    ("hammer", "🔨"),
+    ("hammer_and_wrench", "🛠️"),
+    ("hand", "✋"),
+    ("handshake", "🤝"),
+    ("hankey", "💩"),
+    ("hatching_chick", "🐣"),
+    ("headphones", "🎧"),
+    ("heart", "❤️"),
+    ("heart_eyes", "😍"),
+    ("heartbeat", "💓"),
+    ("heavy_check_mark", "✔️"),
+    ("heavy_minus_sign", "➖"),
+    ("heavy_plus_sign", "➕"),
+    ("hibiscus", "🌺"),
+    ("high_brightness", "🔆"),
+    ("honeybee", "🐝"),
+    ("horse", "🐴"),
+    ("hospital", "🏥"),
+    ("hourglass", "⌛"),
+    ("house", "🏠"),
+    ("hugs", "🤗"),
+    ("hushed", "😯"),
+    ("ice_cream", "🍨"),
+    ("information_source", "ℹ️"),
+    ("innocent", "😇"),
+    ("iphone", "📱"),
+    ("jack_o_lantern", "🎃"),
+    ("jeans", "👖"),
+    ("joy", "😂"),
+    ("key", "🔑"),
+    ("keyboard", "⌨️"),
+    ("kiss", "💋"),
+    ("kissing", "😗"),
+    ("kissing_heart", "😘"),
+    ("koala", "🐨"),
+    ("label", "🏷️"),
+    ("ladybug", "🐞"),
+    ("laughing", "😆"),
+    ("leaves", "🍃"),
+    ("lemon", "🍋"),
+    ("light_rail", "🚈"),
+    ("link", "🔗"),
+    ("lion", "🦁"),
+    ("lipstick", "💄"),
+    ("lock", "🔒"),
+    ("lollipop", "🍭"),
+    ("loudspeaker", "📢"),
+    ("love_letter", "💌"),
+    ("mag", "🔍"),
+    ("mailbox", "📫"),
+    ("man", "👨"),
+    ("maple_leaf", "🍁"),
+    ("mask", "😷"),
+    ("medal_sports", "🏅"),
+    ("mega", "📣"),
+    ("memo", "📝"),
+    ("microphone", "🎤"),
+    ("microscope", "🔬"),
+    ("money_with_wings", "💸"),
+    ("monkey", "🐒"),
+    ("moon", "🌔"),
+    ("mortar_board", "🎓"),
+    ("mouse", "🐭"),
+    ("movie_camera", "🎥"),
+    ("muscle", "💪"),
+    ("mushroom", "🍄"),
+    ("musical_note", "🎵"),
+    ("nerd_face", "🤓"),
+    ("neutral_face", "😐"),
+    ("new", "🆕"),
+    ("newspaper", "📰"),
+    ("no_entry", "⛔"),
+    ("no_entry_sign", "🚫"),
+    ("nose", "👃"),
+    ("notebook", "📓"),
+    ("ok", "🆗"),
+    ("ok_hand", "👌"),
+    ("ok_woman", "🙆"),
+    ("open_mouth", "😮"),
+    ("orange_book", "📙"),
+    ("package", "📦"),
+    ("page_facing_up", "📄"),
+    ("palm_tree", "🌴"),
+    ("panda_face", "🐼"),
+    ("paperclip", "📎"),
+    ("partly_sunny", "⛅"),
+    ("party_popper", "🎉"),
+    ("pencil", "📝"),
+    ("pencil2", "✏️"),
+    ("penguin", "🐧"),
+    ("pensive", "😔"),
+    ("persevere", "😣"),
+    ("phone", "☎️"),
+    ("pig", "🐷"),
+    ("pill", "💊"),
+    ("pineapple", "🍍"),
+    ("pizza", "🍕"),
+    ("point_down", "👇"),
+    ("point_left", "👈"),
+    ("point_right", "👉"),
+    ("point_up", "☝️"),
+    ("poop", "💩"),
+    ("popcorn", "🍿"),
+    ("pouting_cat", "😾"),
+    ("pray", "🙏"),
+    ("purple_heart", "💜"),
+    ("pushpin", "📌"),
+    ("question", "❓"),
+    ("rabbit", "🐰"),
+    ("racehorse", "🐎"),
+    ("rainbow", "🌈"),
+    ("raised_hands", "🙌"),
+    ("ramen", "🍜"),
+    ("recycle", "♻️"),
+    ("red_circle", "🔴"),
+    ("registered", "®️"),
+    ("relaxed", "☺️"),
+    ("relieved", "😌"),
+    ("repeat", "🔁"),
+    ("rice", "🍚"),
+    ("robot", "🤖"),
+    ("rocket", "🚀"),
+    ("rofl", "🤣"),
+    ("rose", "🌹"),
+    ("rotating_light", "🚨"),
+    ("runner", "🏃"),
+    ("running", "🏃"),
+    ("sake", "🍶"),
+    ("satellite", "📡"),
+    ("scissors", "✂️"),
+    ("scream", "😱"),
+    ("see_no_evil", "🙈"),
+    ("seedling", "🌱"),
+    ("shield", "🛡️"),
+    ("shipit", "🐿️"),
+    ("shrug", "🤷"),
+    ("skull", "💀"),
+    ("sleeping", "😴"),
+    ("sleepy", "😪"),
+    ("slightly_frowning_face", "🙁"),
+    ("slightly_smiling_face", "🙂"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("smiling_imp", "😈"),
+    ("smirk", "😏"),
+    ("snail", "🐌"),
+    ("snake", "🐍"),
+    ("sneezing_face", "🤧"),
+    ("snowflake", "❄️"),
+    ("snowman", "⛄"),
+    ("sob", "😭"),
+    ("soccer", "⚽"),
+    ("sparkles", "✨"),
+    ("sparkling_heart", "💖"),
+    ("speak_no_evil", "🙊"),
+    ("speech_balloon", "💬"),
+    ("star", "⭐"),
+    ("star2", "🌟"),
+    ("star_struck", "🤩"),
+    ("stars", "🌠"),
+    ("stop_sign", "🛑"),
+    ("strawberry", "🍓"),
+    ("stuck_out_tongue", "😛"),
+    ("stuck_out_tongue_winking_eye", "😜"),
+    ("sun_with_face", "🌞"),
+    ("sunflower", "🌻"),
+    ("sunglasses", "😎"),
+    ("sunny", "☀️"),
+    ("sweat", "😓"),
+    ("sweat_drops", "💦"),
+    ("sweat_smile", "😅"),
+    ("tada", "🎉"),
+    ("taxi", "🚕"),
+    ("tea", "🍵"),
+    ("tent", "⛺"),
+    ("thinking", "🤔"),
+    ("thought_balloon", "💭"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("ticket", "🎫"),
+    ("tiger", "🐯"),
+    ("tired_face", "😫"),
+    ("tm", "™️"),
+    ("toilet", "🚽"),
+    ("tomato", "🍅"),
+    ("tongue", "👅"),
+    ("tophat", "🎩"),
+    ("tractor", "🚜"),
+    ("traffic_light", "🚥"),
+    ("train", "🚋"),
+    ("trophy", "🏆"),
+    ("truck", "🚚"),
+    ("tulip", "🌷"),
+    ("turtle", "🐢"),
+    ("tv", "📺"),
+    ("two_hearts", "💕"),
+    ("umbrella", "☔"),
+    ("unamused", "😒"),
+    ("unicorn", "🦄"),
+    ("unlock", "🔓"),
+    ("upside_down_face", "🙃"),
+    ("v", "✌️"),
+    ("vertical_traffic_light", "🚦"),
+    ("video_game", "🎮"),
+    ("violin", "🎻"),
+    ("volcano", "🌋"),
+    ("warning", "⚠️"),
+    ("watch", "⌚"),
+    ("watermelon", "🍉"),
+    ("wave", "👋"),
+    ("weary", "😩"),
+    ("whale", "🐳"),
+    ("wheelchair", "♿"),
+    ("white_check_mark", "✅"),
+    ("wink", "😉"),
+    ("wolf", "🐺"),
+    ("woman", "👩"),
+    ("worried", "😟"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("yellow_heart", "💛"),
+    ("yum", "😋"),
+    ("zap", "⚡"),
+    ("zipper_mouth_face", "🤐"),
+    ("zzz", "💤"),
+];
+
+pub fn lookup(name: &str) -> Option<&'static str> {
+    find(name).map(|(_, emoji)| emoji)
+}
+
+fn find(name: &str) -> Option<(&'static str, &'static str)> {
+    let index = SHORTCODES.binary_search_by(|(code, _)| (*code).cmp(name)).ok()?;
+    Some(SHORTCODES[index])
+}
+
+/// `i` 处是 `:`，返回短代码的名字、emoji 和结束位置；未知的短代码返回 `None`
+pub fn scan(line: &str, i: usize) -> Option<(&'static str, &'static str, usize)> {
+    let start = i + 1;
+    let len = line.as_bytes()[start..]
+        .iter()
+        .take_while(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || matches!(b, b'_' | b'+' | b'-'))
+        .count();
+    if len == 0 || line.as_bytes().get(start + len) != Some(&b':') {
+        return None;
+    }
+    let (name, emoji) = find(&line[start..start + len])?;
+    Some((name, emoji, start + len + 1))
+}
+
+/// 图片模板中的 `{name}` 替换为短代码名，`{code}` 替换为小写十六进制码位，
+/// 多个码位用 `-` 连接（不含变体选择符），与 GitHub 的图片文件名一致
+pub fn image_url(template: &str, name: &str, emoji: &str) -> String {
+    let code = emoji
+        .chars()
+        .filter(|c| *c != '\u{FE0F}')
+        .map(|c| format!("{:x}", c as u32))
+        .collect::<Vec<_>>()
+        .join("-");
+    template.replace("{name}", name).replace("{code}", &code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::{EmojiMode, Options}, parser::{tests::render_with, ParserImpl}};
+
+    #[test]
+    fn table_is_sorted_and_scannable() {
+        assert!(SHORTCODES.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(lookup("+1"), Some("👍"));
+        let line = ":tada: :not_an_emoji:";
+        assert_eq!(scan(line, 0), Some(("tada", "🎉", 6)));
+        assert_eq!(scan(line, 7), None);
+        assert_eq!(image_url("/e/{code}.png?{name}", "heart", "❤️"), "/e/2764.png?heart");
+    }
+
+    #[test]
+    fn shortcodes_render_as_unicode_or_images() {
+        let render = |emoji: EmojiMode| {
+            let options = Options {
+                emoji,
+                ..Options::default()
+            };
+            render_with(ParserImpl::new("ok :+1: `:+1:` :nope:".as_bytes()), &options)
+        };
+        assert_eq!(render(EmojiMode::Unicode), "<p>ok 👍 <code>:+1:</code> :nope:</p>");
+        assert_eq!(
+            render(EmojiMode::Image("/e/{code}.png".to_string())),
+            "<p>ok <img src=\"/e/1f44d.png\" alt=\"👍\" title=\":+1:\" class=\"emoji\"> <code>:+1:</code> :nope:</p>"
+        );
+        assert_eq!(render(EmojiMode::Off), "<p>ok :+1: <code>:+1:</code> :nope:</p>");
+    }
+}
@@ -1,26 +1,136 @@
-use crate::{error_handler::ErrorHandler, mapper::Mapper, parser::Parser, schema::Schema, writer::Writer};
+use crate::{autolink::AutolinkOptions, directive::DirectiveHandler, error_handler::ErrorHandler, include::IncludeOptions, mapper::Mapper, numbering::NumberingOptions, options::{HeadingIdOptions, Options}, parser::{AstParser, Parser, ParserError}, push::PushParser, schema::Schema, toc::TocOptions, variables::VariableOptions, visit::Transform, wiki::WikiResolver, writer::Writer};
 
 pub struct MrakdownEngine {
-
+    options: Options,
+    // 按注册顺序在解析和输出之间执行
+    transforms: Vec<Box<dyn Transform>>,
 }
 
 impl MrakdownEngine {
     
     pub fn new() -> Self {
         Self {  
-            
+            options: Options::default(),
+            transforms: Vec::new(),
+        }
+    }
+
+    pub fn with_options(options: Options) -> Self {
+        Self {
+            options,
+            transforms: Vec::new(),
         }
     }
 
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut Options {
+        &mut self.options
+    }
+
+    /// 替换 `> [!KIND]` / `!!! kind` 可用的提示框类型
+    pub fn set_alert_kinds<I, K>(&mut self, kinds: I)
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.options.alert_kinds = kinds.into_iter().map(Into::into).collect();
+    }
+
+    /// 注册 `:name`、`::name`、`:::name` 指令的处理函数，同名的会被替换
+    pub fn register_directive<H: DirectiveHandler + 'static>(&mut self, name: impl Into<String>, handler: H) {
+        self.options.directives.register(name, handler);
+    }
+
+    /// 开启 `[[Page]]` 维基链接，页面名由 `resolver` 转换为 URL
+    pub fn set_wiki_resolver<R: WikiResolver + 'static>(&mut self, resolver: R) {
+        self.options.wiki_resolver = Some(Box::new(resolver));
+    }
+
+    /// 开启 `[TOC]` 目录，`TocOptions::prepend` 为真时没有标记也在开头输出
+    pub fn set_toc(&mut self, toc: TocOptions) {
+        self.options.toc = Some(toc);
+    }
+
+    /// 为标题生成 id，可以选择 slug 算法以及是否输出锚点链接
+    pub fn set_heading_ids(&mut self, heading_ids: HeadingIdOptions) {
+        self.options.heading_ids = Some(heading_ids);
+    }
+
+    /// 为标题加上 `1.2` 这样的层级编号
+    pub fn set_heading_numbers(&mut self, numbering: NumberingOptions) {
+        self.options.heading_numbers = Some(numbering);
+    }
+
+    /// 按 URL 模板链接 `#123`、`org/repo#123`、`@user` 和提交 SHA
+    pub fn set_autolink(&mut self, autolink: AutolinkOptions) {
+        self.options.autolink = Some(autolink);
+    }
+
+    /// 展开 `{{#include path}}`，被包含的文件不能在 `include.root` 之外
+    pub fn set_include(&mut self, include: IncludeOptions) {
+        self.options.include = Some(include);
+    }
+
+    /// 开启 `{{ name }}` 变量替换
+    pub fn set_variables(&mut self, variables: VariableOptions) {
+        self.options.variables = Some(variables);
+    }
+
+    /// 定义一个变量，没有开启变量替换时按默认配置开启
+    pub fn set_variable(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.options
+            .variables
+            .get_or_insert_with(VariableOptions::default)
+            .values
+            .insert(key.into(), value.into());
+    }
+
+    /// 注册一个文档树变换，由 `start_with_transforms` 解析出整篇文档、变换后再输出
+    pub fn register_transform<T: Transform + 'static>(&mut self, transform: T) {
+        self.transforms.push(Box::new(transform));
+    }
+
+    /// 推送式解析，输入通过 `PushParser::feed` 分块交入；不执行注册的变换
+    pub fn push_parser<S: Schema, W: Writer>(&self, w: W, s: S) -> PushParser<'_, S, W> {
+        PushParser::new(Mapper::new(s, w), &self.options)
+    }
+
+    /// 边解析边输出；不执行注册的变换
     pub fn start<P: Parser, S: Schema, W: Writer, H: ErrorHandler>(
         &mut self,
         mut p: P,
         w: W,
-        mut h: H,
+        h: H,
         s: S
     ) {
-        let mapper  = Mapper::new(s, w);
-        let parser_res = p.parse_and_write(mapper);
+        let parser_res = p.parse_and_write_with(Mapper::new(s, w), &self.options);
+        Self::report(&mut p, parser_res, h);
+    }
+
+    /// 先解析出整篇文档，依次执行注册的变换后再输出
+    pub fn start_with_transforms<P: AstParser, S: Schema, W: Writer, H: ErrorHandler>(
+        &mut self,
+        mut p: P,
+        w: W,
+        h: H,
+        s: S
+    ) {
+        let parser_res = p.parse_to_ast(&self.options).and_then(|mut doc| {
+            for transform in &mut self.transforms {
+                transform.transform(&mut doc);
+            }
+            doc.render(Mapper::new(s, w), &self.options).map_err(ParserError::IoError)
+        });
+        Self::report(&mut p, parser_res, h);
+    }
+
+    fn report<P: Parser, H: ErrorHandler>(p: &mut P, parser_res: Result<(), ParserError>, mut h: H) {
+        for d in p.take_diagnostics() {
+            h.handle_diagnostic(d);
+        }
 
         match parser_res {
             Ok(_) => {
@@ -30,4 +140,10 @@ impl MrakdownEngine {
             Err(e) => h.handle_error(e),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Default for MrakdownEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,7 +1,24 @@
-use crate::parser::ParserError;
+use std::path::PathBuf;
+
+use crate::{parser::ParserError, CowStr};
 
 pub trait ErrorHandler {
     fn handle_error(&mut self, e: ParserError);
+
+    /// 不影响输出的问题，例如找不到维基链接指向的页面
+    fn handle_diagnostic(&mut self, d: Diagnostic) {
+        let _ = d;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub msg: CowStr,
+    pub line_num: u32,
+    /// 从 1 开始按字符计数，不知道时为 0
+    pub column_num: u32,
+    /// 出自被包含的文件时为该文件，行号和列号按该文件计算
+    pub file: Option<PathBuf>,
 }
 
 pub struct ErrorHandlerImpl {
@@ -22,4 +39,19 @@ impl ErrorHandler for ErrorHandlerImpl {
         println!("{:?}", e);
         panic!()
     }
-}
\ No newline at end of file
+
+    fn handle_diagnostic(&mut self, d: Diagnostic) {
+        let file = d.file.map(|file| format!("{}: ", file.display())).unwrap_or_default();
+        if d.column_num > 0 {
+            println!("{}line {}:{}: {}", file, d.line_num, d.column_num, d.msg);
+        } else {
+            println!("{}line {}: {}", file, d.line_num, d.msg);
+        }
+    }
+}
+
+impl Default for ErrorHandlerImpl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
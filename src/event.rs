@@ -0,0 +1,532 @@
+//! 事件流：把文档表示为 `Start(Tag)` / `End(Tag)` 以及其间的文本等事件，
+//! 可以用普通的迭代器适配器过滤、改写，再交给 `render` 或自己的输出逻辑。
+//!
+//! `ParserImpl::into_events` 按需读取输入，每读完一个顶层块就产出它的事件；
+//! `Document::events` 从已经建好的文档树产生同样的事件。`Events::next_spanned` 和
+//! `Document::spanned_events` 同时给出每个事件所属节点在源文件中的范围，交给
+//! `render_spanned` 时可以输出 `data-sourcepos`。
+use std::{collections::VecDeque, io::{Read, Result as IoResult}};
+
+use crate::{
+    abbr::{self, Abbreviation},
+    ast::{Block, BlockKind, Document, Inline, InlineKind},
+    attributes::Attributes,
+    directive::{Directive, DirectiveKind},
+    error_handler::Diagnostic,
+    mapper::Mapper,
+    math::MathKind,
+    options::Options,
+    parser::{Parser, ParserError, ParserImpl},
+    ruby::Ruby,
+    schema::Schema,
+    tokenizer::Span,
+    units,
+    writer::Writer,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Start(Tag),
+    End(Tag),
+    Text(String),
+    Code(String),
+    /// 段落中相邻两行之间的换行
+    SoftBreak,
+    /// 输出为 `<br>` 的换行，解析不会产生，供改写事件时插入
+    HardBreak,
+    /// 单独的空行
+    BlankLine,
+    /// 原样输出的 HTML，解析不会产生，供改写事件时插入
+    Html(String),
+    Math(MathKind, String),
+    Emoji {
+        name: String,
+        emoji: String,
+    },
+    Image {
+        src: String,
+        alt: String,
+        title: Option<String>,
+        attrs: Attributes,
+    },
+    Ruby(Vec<(String, String)>),
+    /// `[TOC]`
+    TocMarker,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tag {
+    Paragraph(Attributes),
+    Heading {
+        level: u8,
+        attrs: Attributes,
+    },
+    CodeBlock {
+        lang: Option<String>,
+        attrs: Attributes,
+    },
+    Alert {
+        kind: String,
+        title: Option<String>,
+        attrs: Attributes,
+    },
+    Directive {
+        kind: DirectiveKind,
+        name: String,
+        label: Option<String>,
+        attributes: Attributes,
+    },
+    Emphasis(u8),
+    Link {
+        dest: String,
+        title: Option<String>,
+        attrs: Attributes,
+    },
+    /// 开启缩写时文本中完整出现的缩写，其中是缩写本身的文本
+    Abbreviation {
+        title: String,
+    },
+}
+
+impl Document {
+    pub fn events(&self) -> impl Iterator<Item = Event> {
+        self.spanned_events().map(|(event, _)| event)
+    }
+
+    /// 事件及其所属节点在源文件中的范围，一段文本拆成的多个事件共用文本节点的范围
+    pub fn spanned_events(&self) -> std::vec::IntoIter<(Event, Span)> {
+        let mut events = VecDeque::new();
+        push_blocks(&self.blocks, &self.abbreviations, &mut events);
+        Vec::from(events).into_iter()
+    }
+}
+
+/// 按需解析的事件迭代器，由 `ParserImpl::into_events` 创建
+pub struct Events<'o, R: Read> {
+    parser: ParserImpl<R>,
+    options: &'o Options,
+    queue: VecDeque<(Event, Span)>,
+    error: Option<ParserError>,
+}
+
+impl<R: Read> ParserImpl<R> {
+    pub fn into_events(self, options: &Options) -> Events<'_, R> {
+        Events {
+            parser: self,
+            options,
+            queue: VecDeque::new(),
+            error: None,
+        }
+    }
+}
+
+impl<R: Read> Events<'_, R> {
+    /// 读取或解析出错时迭代提前结束，错误保存在这里
+    pub fn take_error(&mut self) -> Option<ParserError> {
+        self.error.take()
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.parser.take_diagnostics()
+    }
+}
+
+impl<R: Read> Events<'_, R> {
+    /// 下一个事件及其所属节点在源文件中的范围
+    pub fn next_spanned(&mut self) -> Option<(Event, Span)> {
+        while self.queue.is_empty() {
+            if self.error.is_some() {
+                return None;
+            }
+            match self.parser.step(self.options) {
+                Ok(more) => {
+                    let blocks = self.parser.take_blocks();
+                    push_blocks(&blocks, self.parser.abbreviations(), &mut self.queue);
+                    if !more && self.queue.is_empty() {
+                        return None;
+                    }
+                }
+                Err(e) => self.error = Some(e),
+            }
+        }
+        self.queue.pop_front()
+    }
+}
+
+impl<R: Read> Iterator for Events<'_, R> {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.next_spanned().map(|(event, _)| event)
+    }
+}
+
+fn push_blocks(blocks: &[Block], abbrs: &[Abbreviation], events: &mut VecDeque<(Event, Span)>) {
+    for block in blocks {
+        let span = block.span;
+        match &block.kind {
+            BlockKind::Paragraph { attrs, children } => {
+                wrap(Tag::Paragraph(attrs.clone()), span, events, |events| push_inlines(children, abbrs, events))
+            }
+            BlockKind::Heading { level, attrs, children } => {
+                let tag = Tag::Heading {
+                    level: *level,
+                    attrs: attrs.clone(),
+                };
+                wrap(tag, span, events, |events| push_inlines(children, abbrs, events))
+            }
+            BlockKind::CodeBlock { lang, attrs, code } => {
+                let tag = Tag::CodeBlock {
+                    lang: lang.clone(),
+                    attrs: attrs.clone(),
+                };
+                wrap(tag, span, events, |events| events.push_back((Event::Text(code.clone()), span)))
+            }
+            BlockKind::Math(tex) => events.push_back((Event::Math(MathKind::Block, tex.clone()), span)),
+            BlockKind::Alert { kind, title, attrs, children } => {
+                let tag = Tag::Alert {
+                    kind: kind.clone(),
+                    title: title.clone(),
+                    attrs: attrs.clone(),
+                };
+                wrap(tag, span, events, |events| push_blocks(children, abbrs, events))
+            }
+            BlockKind::Directive(node) => {
+                let tag = Tag::Directive {
+                    kind: DirectiveKind::Container,
+                    name: node.name.clone(),
+                    label: node.label.clone(),
+                    attributes: node.attributes.clone(),
+                };
+                wrap(tag, span, events, |events| push_blocks(&node.children, abbrs, events))
+            }
+            BlockKind::LeafDirective(node) => {
+                let tag = Tag::Directive {
+                    kind: DirectiveKind::Leaf,
+                    name: node.name.clone(),
+                    label: node.label.clone(),
+                    attributes: node.attributes.clone(),
+                };
+                wrap(tag, span, events, |events| push_inlines(&node.children, abbrs, events))
+            }
+            BlockKind::TocMarker => events.push_back((Event::TocMarker, span)),
+            BlockKind::BlankLine => events.push_back((Event::BlankLine, span)),
+        }
+    }
+}
+
+fn push_inlines(inlines: &[Inline], abbrs: &[Abbreviation], events: &mut VecDeque<(Event, Span)>) {
+    for inline in inlines {
+        let span = inline.span;
+        match &inline.kind {
+            InlineKind::Text(text) => {
+                // 软换行单独作为事件
+                for (i, line) in text.split('\n').enumerate() {
+                    if i > 0 {
+                        events.push_back((Event::SoftBreak, span));
+                    }
+                    push_text(line, span, abbrs, events);
+                }
+            }
+            InlineKind::Code(code) => events.push_back((Event::Code(code.clone()), span)),
+            InlineKind::Emphasis { level, children } => {
+                wrap(Tag::Emphasis(*level), span, events, |events| push_inlines(children, abbrs, events))
+            }
+            InlineKind::Math { kind, tex } => events.push_back((Event::Math(*kind, tex.clone()), span)),
+            InlineKind::Emoji { name, emoji } => {
+                let emoji = Event::Emoji {
+                    name: name.clone(),
+                    emoji: emoji.clone(),
+                };
+                events.push_back((emoji, span))
+            }
+            InlineKind::Link { dest, title, attrs, children } => {
+                let tag = Tag::Link {
+                    dest: dest.clone(),
+                    title: title.clone(),
+                    attrs: attrs.clone(),
+                };
+                // 与直接输出相同，链接中不替换缩写
+                wrap(tag, span, events, |events| push_inlines(children, &[], events))
+            }
+            InlineKind::Image { src, alt, title, attrs } => {
+                let image = Event::Image {
+                    src: src.clone(),
+                    alt: alt.clone(),
+                    title: title.clone(),
+                    attrs: attrs.clone(),
+                };
+                events.push_back((image, span))
+            }
+            InlineKind::Ruby(groups) => events.push_back((Event::Ruby(groups.clone()), span)),
+            InlineKind::Directive(node) => {
+                let tag = Tag::Directive {
+                    kind: DirectiveKind::Text,
+                    name: node.name.clone(),
+                    label: node.label.clone(),
+                    attributes: node.attributes.clone(),
+                };
+                wrap(tag, span, events, |events| push_inlines(&node.children, abbrs, events))
+            }
+        }
+    }
+}
+
+// 一行文本，完整出现的缩写包在 `Tag::Abbreviation` 中
+fn push_text(line: &str, span: Span, abbrs: &[Abbreviation], events: &mut VecDeque<(Event, Span)>) {
+    let mut start = 0;
+    let mut i = 0;
+    while i < line.len() {
+        match abbr::find_at(line, i, abbrs) {
+            Some(found) => {
+                if start < i {
+                    events.push_back((Event::Text(line[start..i].to_string()), span));
+                }
+                let tag = Tag::Abbreviation {
+                    title: found.title.clone(),
+                };
+                wrap(tag, span, events, |events| events.push_back((Event::Text(found.abbr.clone()), span)));
+                i += found.abbr.len();
+                start = i;
+            }
+            None => i = units::next_char(line, i),
+        }
+    }
+    if start < line.len() {
+        events.push_back((Event::Text(line[start..].to_string()), span));
+    }
+}
+
+fn wrap(tag: Tag, span: Span, events: &mut VecDeque<(Event, Span)>, children: impl FnOnce(&mut VecDeque<(Event, Span)>)) {
+    events.push_back((Event::Start(tag.clone()), span));
+    children(events);
+    events.push_back((Event::End(tag), span));
+}
+
+/// 把事件输出到 `mapper`；事件不带位置，开启 `source_positions` 时用 `render_spanned`
+pub fn render<I, S, W>(events: I, mapper: Mapper<S, W>, options: &Options) -> IoResult<()>
+where
+    I: IntoIterator<Item = Event>,
+    S: Schema,
+    W: Writer,
+{
+    write_document(events.into_iter().map(|event| (event, None)), mapper, options)
+}
+
+/// 同 `render`，块的开始事件按其范围输出 `data-sourcepos`
+pub fn render_spanned<I, S, W>(events: I, mapper: Mapper<S, W>, options: &Options) -> IoResult<()>
+where
+    I: IntoIterator<Item = (Event, Span)>,
+    S: Schema,
+    W: Writer,
+{
+    write_document(events.into_iter().map(|(event, span)| (event, Some(span))), mapper, options)
+}
+
+fn write_document<S: Schema, W: Writer>(
+    events: impl Iterator<Item = (Event, Option<Span>)>,
+    mut mapper: Mapper<S, W>,
+    options: &Options,
+) -> IoResult<()> {
+    mapper.apply_options(options);
+    if options.generates_heading_ids() {
+        // 手写的标题 id 可能出现在后面，先收下所有事件
+        let events: Vec<_> = events.collect();
+        mapper.claim_heading_ids(events.iter().filter_map(|(event, _)| match event {
+            Event::Start(Tag::Heading { attrs, .. }) => attrs.id.as_deref(),
+            _ => None,
+        }));
+        write_events(events, &mut mapper, options)?;
+    } else {
+        write_events(events, &mut mapper, options)?;
+    }
+    mapper.flush()
+}
+
+fn write_events<S: Schema, W: Writer>(
+    events: impl IntoIterator<Item = (Event, Option<Span>)>,
+    mapper: &mut Mapper<S, W>,
+    options: &Options,
+) -> IoResult<()> {
+    let mut code_block = false;
+    for (event, span) in events {
+        if let Some(span) = span.filter(|_| starts_block(&event)) {
+            mapper.set_line_span(span);
+        }
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Paragraph(attrs) => mapper.write_html_paragraph_start(&attrs)?,
+                Tag::Heading { level, attrs } => mapper.write_html_header_start(level, &attrs)?,
+                Tag::CodeBlock { lang, attrs } => {
+                    code_block = true;
+                    mapper.write_html_code_block_start(lang.as_deref(), &attrs)?;
+                }
+                Tag::Alert { kind, title, attrs } => {
+                    let title = title.as_deref();
+                    mapper.write_html_alert_start(&kind, title, &attrs)?;
+                }
+                Tag::Directive { .. } => mapper.begin_capture(),
+                Tag::Emphasis(level) => mapper.write_html_emphasis_start(level)?,
+                Tag::Link { dest, title, attrs } => mapper.write_html_link_start(&dest, title.as_deref(), &attrs)?,
+                Tag::Abbreviation { title } => mapper.write_html_abbr_start(&title)?,
+            },
+            Event::End(tag) => match tag {
+                Tag::Paragraph(_) => mapper.write_html_paragraph_end()?,
+                Tag::Heading { level, .. } => {
+                    mapper.write_html_header_end(level)?;
+                    mapper.write_html_new_line()?;
+                }
+                Tag::CodeBlock { .. } => {
+                    code_block = false;
+                    mapper.write_html_code_block_end()?;
+                }
+                Tag::Alert { .. } => mapper.write_html_alert_end()?,
+                Tag::Directive { kind, name, label, attributes } => {
+                    let children = mapper.end_capture();
+                    if let Some(handler) = options.directives.get(&name) {
+                        let directive = Directive {
+                            kind,
+                            name: &name,
+                            label: label.as_deref(),
+                            attributes: &attributes,
+                            children: &children,
+                        };
+                        mapper.write_html_directive(handler, &directive)?;
+                    }
+                }
+                Tag::Emphasis(level) => mapper.write_html_emphasis_end(level)?,
+                Tag::Link { .. } => mapper.write_html_link_end()?,
+                Tag::Abbreviation { .. } => mapper.write_html_abbr_end()?,
+            },
+            Event::Text(text) if code_block => mapper.write_html_escaped(&text)?,
+            Event::Text(text) => mapper.write_html_text(&text)?,
+            Event::Code(code) => mapper.write_html_code(&code)?,
+            Event::SoftBreak => mapper.write_html_content(b"\n")?,
+            Event::HardBreak | Event::BlankLine => mapper.write_html_new_line()?,
+            Event::Html(html) => mapper.write_html_content(html.as_bytes())?,
+            Event::Math(kind, tex) => mapper.write_html_math(&tex, kind, options.math)?,
+            Event::Emoji { name, emoji } => mapper.write_html_emoji(&name, &emoji, &options.emoji)?,
+            Event::Image { src, alt, title, attrs } => mapper.write_html_image(&src, &alt, title.as_deref(), &attrs)?,
+            Event::Ruby(groups) => {
+                let ruby = Ruby {
+                    groups: groups.iter().map(|(base, text)| (base.as_str(), text.as_str())).collect(),
+                };
+                mapper.write_html_ruby(&ruby)?;
+            }
+            Event::TocMarker => mapper.write_html_toc_marker()?,
+        }
+    }
+    Ok(())
+}
+
+// 开始一个块的事件，输出前要把块的范围交给 `mapper`
+fn starts_block(event: &Event) -> bool {
+    match event {
+        Event::Start(tag) => matches!(
+            tag,
+            Tag::Paragraph(_)
+                | Tag::Heading { .. }
+                | Tag::CodeBlock { .. }
+                | Tag::Alert { .. }
+                | Tag::Directive {
+                    kind: DirectiveKind::Container | DirectiveKind::Leaf,
+                    ..
+                }
+        ),
+        Event::Math(MathKind::Block, _) | Event::BlankLine | Event::TocMarker => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::AstParser, schema::HtmlSchema};
+
+    #[test]
+    fn events_can_be_filtered_and_rendered() {
+        let src = "# 标题\n\n第一行 *强调*\n第二行 [链接](https://a.com)\n\n```\na < b\n```\n";
+        let options = Options {
+            soft_breaks: true,
+            ..Options::default()
+        };
+        let events: Vec<Event> = ParserImpl::new(src.as_bytes()).into_events(&options).collect();
+        assert_eq!(
+            &events[..4],
+            &[
+                Event::Start(Tag::Heading {
+                    level: 1,
+                    attrs: Attributes::default()
+                }),
+                Event::Text("标题".to_string()),
+                Event::End(Tag::Heading {
+                    level: 1,
+                    attrs: Attributes::default()
+                }),
+                Event::Start(Tag::Paragraph(Attributes::default())),
+            ]
+        );
+        assert!(events.contains(&Event::SoftBreak));
+        // 块之间的分隔不是换行事件
+        assert!(!events.contains(&Event::HardBreak));
+
+        let mut streamed = Vec::new();
+        ParserImpl::new(src.as_bytes())
+            .parse_and_write_with(Mapper::new(HtmlSchema::new(), &mut streamed), &options)
+            .unwrap();
+        let mut rendered = Vec::new();
+        render(events.iter().cloned(), Mapper::new(HtmlSchema::new(), &mut rendered), &options).unwrap();
+        assert_eq!(rendered, streamed);
+
+        // 去掉强调和链接，只保留其中的文字
+        let plain = events.into_iter().filter(|e| {
+            !matches!(e, Event::Start(Tag::Emphasis(_) | Tag::Link { .. }) | Event::End(Tag::Emphasis(_) | Tag::Link { .. }))
+        });
+        let mut out = Vec::new();
+        render(plain, Mapper::new(HtmlSchema::new(), &mut out), &options).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "<h1>标题</h1><br><p>第一行 强调\n第二行 链接</p><pre><code>a &lt; b\n</code></pre>"
+        );
+
+        // 缩写也作为事件给出，输出与直接解析相同
+        let src = "# HTML 标题\n\n*HTML* 和 HTMLX\n[HTML](https://a.com) HTML\n\n*[HTML]: Hyper Text\n";
+        let options = Options {
+            abbreviations: true,
+            ..Options::default()
+        };
+        let events: Vec<Event> = ParserImpl::new(src.as_bytes()).into_events(&options).collect();
+        let abbr = Tag::Abbreviation {
+            title: "Hyper Text".to_string(),
+        };
+        assert_eq!(events.iter().filter(|e| **e == Event::Start(abbr.clone())).count(), 3);
+        let mut streamed = Vec::new();
+        ParserImpl::new(src.as_bytes())
+            .parse_and_write_with(Mapper::new(HtmlSchema::new(), &mut streamed), &options)
+            .unwrap();
+        let mut rendered = Vec::new();
+        render(events, Mapper::new(HtmlSchema::new(), &mut rendered), &options).unwrap();
+        assert_eq!(String::from_utf8(rendered).unwrap(), String::from_utf8(streamed).unwrap());
+        let tree = ParserImpl::new(src.as_bytes()).parse_to_ast(&options).unwrap();
+        assert_eq!(tree.events().filter(|e| *e == Event::Start(abbr.clone())).count(), 3);
+
+        // 带上范围的事件可以输出 `data-sourcepos`
+        let src = "# 标题\n\n> [!NOTE]\n> 提示 *强调*\n\n```\ncode\n```\n";
+        let options = Options {
+            source_positions: true,
+            ..Options::default()
+        };
+        let mut streamed = Vec::new();
+        ParserImpl::new(src.as_bytes())
+            .parse_and_write_with(Mapper::new(HtmlSchema::new(), &mut streamed), &options)
+            .unwrap();
+        let mut events = ParserImpl::new(src.as_bytes()).into_events(&options);
+        let mut rendered = Vec::new();
+        let spanned = std::iter::from_fn(|| events.next_spanned());
+        render_spanned(spanned, Mapper::new(HtmlSchema::new(), &mut rendered), &options).unwrap();
+        assert_eq!(String::from_utf8(rendered).unwrap(), String::from_utf8(streamed).unwrap());
+        let tree = ParserImpl::new(src.as_bytes()).parse_to_ast(&options).unwrap();
+        let (_, span) = tree.spanned_events().find(|(e, _)| matches!(e, Event::Start(Tag::Emphasis(_)))).unwrap();
+        assert_eq!((span.start.line, span.start.column, span.end.column), (4, 6, 10));
+    }
+}
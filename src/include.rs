@@ -0,0 +1,375 @@
+//! `{{#include path}}` 把另一个文件的内容拼接到 token 流中再解析。支持
+//! `file.rs:10:20` 这样的行号范围（从 1 开始，含两端，任一端可省略）以及
+//! `file.rs:name` 取 `ANCHOR: name` 和 `ANCHOR_END: name` 之间的行。
+//!
+//! 路径相对于所在文件的目录，顶层文档相对于 `root`，解析后不能离开 `root`。
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{Block, Inline},
+    error_handler::Diagnostic,
+    tokenizer::Span,
+    units,
+    visit::{self, VisitMut},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeOptions {
+    pub root: PathBuf,
+    /// 最大嵌套层数
+    pub max_depth: usize,
+}
+
+impl IncludeOptions {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_depth: 16,
+        }
+    }
+}
+
+/// 展开后的一行在原文中的位置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineOrigin {
+    /// 在顶层文档中的行号，被包含的行取 include 指令所在的行
+    pub line: u32,
+    /// 被包含的行所在的文件及其中的行号
+    pub included: Option<(PathBuf, u32)>,
+}
+
+/// 展开后的行号到原文位置的映射，诊断信息和文档树的位置据此换回原文
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineMap {
+    // 下标为展开后的行号减 1
+    origins: Vec<LineOrigin>,
+}
+
+impl LineMap {
+    pub fn origin(&self, line: u32) -> Option<&LineOrigin> {
+        self.origins.get(line.checked_sub(1)? as usize)
+    }
+
+    /// 展开后的第 `line` 行在顶层文档中的行号
+    pub fn line(&self, line: u32) -> u32 {
+        self.origin(line).map_or(line, |origin| origin.line)
+    }
+
+    /// 诊断信息的行号换回原文，来自被包含文件的行带上文件名
+    pub fn remap_diagnostic(&self, diagnostic: &mut Diagnostic) {
+        let Some(origin) = self.origin(diagnostic.line_num) else {
+            return;
+        };
+        match &origin.included {
+            Some((file, line)) => {
+                diagnostic.file = Some(file.clone());
+                diagnostic.line_num = *line;
+            }
+            None => diagnostic.line_num = origin.line,
+        }
+    }
+}
+
+// 文档树中的行号换回顶层文档，偏移和列仍按展开后的文本计算
+impl VisitMut for &LineMap {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        self.remap_span(&mut block.span);
+        visit::walk_block_mut(self, block);
+    }
+
+    fn visit_inline_mut(&mut self, inline: &mut Inline) {
+        self.remap_span(&mut inline.span);
+        visit::walk_inline_mut(self, inline);
+    }
+}
+
+impl LineMap {
+    fn remap_span(&self, span: &mut Span) {
+        span.start.line = self.line(span.start.line);
+        span.end.line = self.line(span.end.line);
+    }
+}
+
+/// 展开文本中的 include，出错时保留原文并记录诊断信息
+pub fn expand(text: &str, options: &IncludeOptions, diagnostics: &mut Vec<Diagnostic>) -> (String, LineMap) {
+    let root = match options.root.canonicalize() {
+        Ok(root) => root,
+        Err(e) => {
+            diagnostics.push(Diagnostic {
+                msg: format!("include root {}: {}", options.root.display(), e).into(),
+                line_num: 0,
+                column_num: 0,
+                file: None,
+            });
+            return (text.to_string(), LineMap::default());
+        }
+    };
+    let mut expander = Expander {
+        max_depth: options.max_depth,
+        stack: Vec::new(),
+        diagnostics,
+    };
+    let (text, origins) = expander.expand(text, &root, &root, None);
+    (text, LineMap { origins })
+}
+
+// 正在展开的文件及其每一行在文件中的行号，顶层文档为 None
+type Source<'a> = Option<(&'a Path, &'a [u32])>;
+
+struct Expander<'a> {
+    max_depth: usize,
+    // 正在展开的文件，用于发现循环引用
+    stack: Vec<PathBuf>,
+    diagnostics: &'a mut Vec<Diagnostic>,
+}
+
+impl Expander<'_> {
+    fn expand(&mut self, text: &str, root: &Path, dir: &Path, source: Source) -> (String, Vec<LineOrigin>) {
+        let mut out = String::with_capacity(text.len());
+        let mut origins = vec![origin(source, 1)];
+        let mut line_num = 1;
+        for (line, ending) in units::lines(text) {
+            self.expand_line(line, &mut out, &mut origins, root, dir, source, line_num);
+            if !ending.is_empty() {
+                out.push_str(ending);
+                line_num += 1;
+                origins.push(origin(source, line_num));
+            }
+        }
+        (out, origins)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn expand_line(
+        &mut self,
+        line: &str,
+        out: &mut String,
+        origins: &mut Vec<LineOrigin>,
+        root: &Path,
+        dir: &Path,
+        source: Source,
+        line_num: u32,
+    ) {
+        let mut rest = line;
+        while let Some((start, spec, end)) = find_directive(rest) {
+            out.push_str(&rest[..start]);
+            match self.include(&spec, root, dir) {
+                Ok((text, mut included)) => {
+                    // 被包含文件末尾的换行由包含它的这一行提供
+                    let text = match ["\r\n", "\n", "\r"].iter().find_map(|ending| text.strip_suffix(ending)) {
+                        Some(text) => {
+                            included.pop();
+                            text
+                        }
+                        None => &text,
+                    };
+                    if !text.is_empty() {
+                        // 拼进来的行都算作指令所在的行
+                        let at = origins.pop().map_or(line_num, |origin| origin.line);
+                        origins.extend(included.into_iter().map(|included| LineOrigin { line: at, included }));
+                    }
+                    out.push_str(text);
+                }
+                Err(msg) => {
+                    let line_num = file_line(source, line_num);
+                    let msg = match source {
+                        Some((file, _)) => format!("{} (included from {}:{})", msg, file.display(), line_num),
+                        None => msg,
+                    };
+                    let offset = line.len() - rest.len() + start;
+                    self.diagnostics.push(Diagnostic {
+                        msg: msg.into(),
+                        line_num,
+                        column_num: line[..offset].chars().count() as u32 + 1,
+                        file: source.map(|(file, _)| file.to_path_buf()),
+                    });
+                    out.push_str(&rest[start..end]);
+                }
+            }
+            rest = &rest[end..];
+        }
+        out.push_str(rest);
+    }
+
+    // 展开后的文本，以及其中每一行所在的文件和行号
+    #[allow(clippy::type_complexity)]
+    fn include(&mut self, spec: &str, root: &Path, dir: &Path) -> Result<(String, Vec<Option<(PathBuf, u32)>>), String> {
+        let (path, range) = parse_spec(spec);
+        let resolved = dir
+            .join(path)
+            .canonicalize()
+            .map_err(|e| format!("cannot include {}: {}", path, e))?;
+        if !resolved.starts_with(root) {
+            return Err(format!("cannot include {}: outside of the include root", path));
+        }
+        if self.stack.contains(&resolved) {
+            return Err(format!("cannot include {}: include cycle", path));
+        }
+        if self.stack.len() >= self.max_depth {
+            return Err(format!("cannot include {}: includes nested too deeply", path));
+        }
+        let content = fs::read_to_string(&resolved).map_err(|e| format!("cannot include {}: {}", path, e))?;
+        let (content, numbers) =
+            select(&content, &range).ok_or_else(|| format!("cannot include {}: no such lines or anchor", spec))?;
+        let parent = resolved.parent().unwrap_or(root).to_path_buf();
+        self.stack.push(resolved.clone());
+        let (text, origins) = self.expand(&content, root, &parent, Some((&resolved, &numbers)));
+        self.stack.pop();
+        Ok((text, origins.into_iter().map(|origin| origin.included).collect()))
+    }
+}
+
+// 第 `line` 行在所在文件中的行号
+fn file_line(source: Source, line: u32) -> u32 {
+    match source {
+        Some((_, numbers)) => numbers.get(line as usize - 1).copied().unwrap_or(line),
+        None => line,
+    }
+}
+
+fn origin(source: Source, line: u32) -> LineOrigin {
+    let line = file_line(source, line);
+    LineOrigin {
+        line,
+        included: source.map(|(file, _)| (file.to_path_buf(), line)),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Range {
+    All,
+    Lines(Option<usize>, Option<usize>),
+    Anchor(String),
+}
+
+// `{{#include spec}}`，返回起止位置
+fn find_directive(line: &str) -> Option<(usize, String, usize)> {
+    let start = units::find(line, "{{#include", 0)?;
+    let close = units::find(line, "}}", start)?;
+    let spec = units::trim(&line[start + 10..close]).to_string();
+    if spec.is_empty() {
+        return None;
+    }
+    Some((start, spec, close + 2))
+}
+
+fn parse_spec(spec: &str) -> (&str, Range) {
+    let mut parts = spec.splitn(3, ':');
+    let path = parts.next().unwrap_or_default();
+    let number = |s: &str| s.trim().parse::<usize>().ok();
+    let range = match (parts.next(), parts.next()) {
+        (None, _) => Range::All,
+        (Some(from), Some(to)) => Range::Lines(number(from), number(to)),
+        (Some(one), None) => match number(one) {
+            Some(n) => Range::Lines(Some(n), Some(n)),
+            None => Range::Anchor(one.trim().to_string()),
+        },
+    };
+    (path, range)
+}
+
+// 取出需要的行，并去掉所有 `ANCHOR:` 标记行；同时返回每一行在文件中的行号
+fn select(content: &str, range: &Range) -> Option<(String, Vec<u32>)> {
+    let is_marker = |line: &str| line.contains("ANCHOR:") || line.contains("ANCHOR_END:");
+    let lines: Vec<(u32, &str)> = (1..).zip(content.split_inclusive('\n')).collect();
+    let picked: &[(u32, &str)] = match range {
+        Range::All => &lines,
+        Range::Lines(from, to) => {
+            let from = from.unwrap_or(1).max(1) - 1;
+            let to = to.unwrap_or(lines.len()).min(lines.len());
+            lines.get(from..to.max(from))?
+        }
+        Range::Anchor(name) => {
+            let start = lines.iter().position(|(_, l)| anchor_name(l, "ANCHOR:") == Some(name))?;
+            let end = start + lines[start..].iter().position(|(_, l)| anchor_name(l, "ANCHOR_END:") == Some(name))?;
+            &lines[start + 1..end]
+        }
+    };
+    let picked = picked.iter().filter(|(_, l)| !is_marker(l));
+    Some((picked.clone().map(|(_, l)| *l).collect(), picked.map(|(n, _)| *n).collect()))
+}
+
+fn anchor_name<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let rest = &line[line.find(marker)? + marker.len()..];
+    rest.split_whitespace().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        options::Options,
+        parser::{tests::render_with_diagnostics, ParserImpl},
+        variables::{UndefinedVariable, VariableOptions},
+    };
+
+    #[test]
+    fn ranges_and_anchors() {
+        let src = "fn a() {}\n// ANCHOR: main\nfn main() {\n}\n// ANCHOR_END: main\nfn b() {}\n";
+        let text = |range: &Range| select(src, range).map(|(text, _)| text);
+        assert_eq!(parse_spec("x.rs:3:4"), ("x.rs", Range::Lines(Some(3), Some(4))));
+        assert_eq!(text(&parse_spec("x.rs:3:4").1).as_deref(), Some("fn main() {\n}\n"));
+        assert_eq!(text(&parse_spec("x.rs:5:").1).as_deref(), Some("fn b() {}\n"));
+        assert_eq!(text(&parse_spec("x.rs:main").1).as_deref(), Some("fn main() {\n}\n"));
+        assert_eq!(text(&Range::All).as_deref(), Some("fn a() {}\nfn main() {\n}\nfn b() {}\n"));
+        assert!(text(&parse_spec("x.rs:nope").1).is_none());
+        // 去掉标记行后，每一行仍对应文件中原来的行号
+        assert_eq!(select(src, &Range::All).map(|(_, lines)| lines), Some(vec![1, 3, 4, 6]));
+    }
+
+    #[test]
+    fn includes_are_spliced_and_sandboxed() {
+        let root = std::env::temp_dir().join(format!("mrakdown-include-{}", std::process::id()));
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("docs/a.md"), "## From A\n{{#include b.md}}\n").unwrap();
+        fs::write(root.join("docs/b.md"), "b {{#include a.md}}\n").unwrap();
+        fs::write(root.join("code.rs"), "fn one() {}\nfn two() {}\nfn three() {}\n").unwrap();
+        let options = Options {
+            include: Some(IncludeOptions::new(root.join("docs"))),
+            ..Options::default()
+        };
+        let src = "{{#include a.md}}\n```rust\n{{#include ../code.rs:2}}\n```\n";
+        let (html, diagnostics) = render_with_diagnostics(ParserImpl::new(src.as_bytes()), &options);
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            html,
+            "<h2>From A</h2><br><p>b {{#include a.md}}</p>\
+             <pre><code class=\"language-rust\">{{#include ../code.rs:2}}\n</code></pre>"
+        );
+        let msgs: Vec<String> = diagnostics.into_iter().map(|d| format!("{}: {}", d.line_num, d.msg)).collect();
+        assert_eq!(msgs.len(), 2);
+        assert!(msgs[0].starts_with("1: cannot include a.md: include cycle (included from "));
+        assert!(msgs[0].ends_with("b.md:1)"));
+        assert_eq!(msgs[1], "3: cannot include ../code.rs: outside of the include root");
+    }
+
+    #[test]
+    fn included_lines_map_back_to_their_files() {
+        let root = std::env::temp_dir().join(format!("mrakdown-include-lines-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("part.md"), "## Part\n{{ nope }}\n").unwrap();
+        let options = Options {
+            include: Some(IncludeOptions::new(&root)),
+            variables: Some(VariableOptions {
+                undefined: UndefinedVariable::Error,
+                ..Default::default()
+            }),
+            source_positions: true,
+            ..Options::default()
+        };
+        let src = "# Top\n{{#include part.md}}\nx {{#include missing.md}}\n{{ nope }}\n";
+        let (html, diagnostics) = render_with_diagnostics(ParserImpl::new(src.as_bytes()), &options);
+        let part = root.join("part.md").canonicalize().unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert!(html.contains("<h2 data-sourcepos=\"2:1-2:"), "{}", html);
+        assert!(html.ends_with("<p data-sourcepos=\"4:1-4:10\">{{ nope }}</p>"), "{}", html);
+        let diagnostics: Vec<(Option<PathBuf>, u32, u32)> =
+            diagnostics.into_iter().map(|d| (d.file, d.line_num, d.column_num)).collect();
+        assert_eq!(diagnostics, vec![(None, 3, 3), (Some(part), 2, 1), (None, 4, 1)]);
+    }
+}
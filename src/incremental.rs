@@ -0,0 +1,362 @@
+//! 增量解析：编辑器每次修改后只重新解析受影响的顶层块，返回文档树和输出中变化的部分。
+//!
+//! 从编辑位置之前最后一个完整的顶层块之后开始重新解析，直到解析出的块与编辑位置之后的某个旧块
+//! 完全相同（位置按编辑平移），其后的块和输出直接沿用。目录、标题 id 和编号、变量、缩写、
+//! include 依赖整篇文档，`data-sourcepos` 中的行号随编辑变化，开启时每次都完整解析。
+use std::{
+    io::{self, Error, ErrorKind},
+    ops::Range,
+};
+
+use u8unit::Position;
+
+use crate::{
+    ast::{self, Block, Inline},
+    mapper::Mapper,
+    options::Options,
+    parser::{AstParser, ParserError, ParserImpl},
+    schema::HtmlSchema,
+    str_parser::StrParser,
+    tokenizer::StrTokenizer,
+    visit::{self, VisitMut},
+};
+
+/// 把 `range` 范围内的原文替换为 `replacement`，范围按字节计算，必须落在字符边界上
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub replacement: String,
+}
+
+/// 一次编辑造成的变化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    /// 被替换的旧顶层块
+    pub old_blocks: Range<usize>,
+    /// 替换后的新顶层块，下标按编辑后的文档计算
+    pub blocks: Range<usize>,
+    /// 被替换的旧输出字节范围，以及替换进去的新输出
+    pub old_output: Range<usize>,
+    pub output: String,
+}
+
+pub struct IncrementalDocument<'o> {
+    options: &'o Options,
+    source: String,
+    blocks: Vec<Block>,
+    // 每个顶层块的输出；完整解析时只有一项，为整篇输出
+    html: Vec<String>,
+    whole: bool,
+}
+
+impl<'o> IncrementalDocument<'o> {
+    pub fn new(source: impl Into<String>, options: &'o Options) -> Result<Self, ParserError> {
+        let source = source.into();
+        let (blocks, html) = parse_all(&source, options)?;
+        Ok(Self {
+            options,
+            source,
+            blocks,
+            html,
+            whole: needs_full_parse(options),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    pub fn output(&self) -> String {
+        self.html.concat()
+    }
+
+    /// 应用一次编辑，返回文档树和输出中变化的部分；范围无效或解析出错时文档保持不变
+    pub fn edit(&mut self, edit: Edit) -> Result<Change, ParserError> {
+        let range = edit.range.clone();
+        if range.start > range.end
+            || !self.source.is_char_boundary(range.start)
+            || !self.source.is_char_boundary(range.end)
+        {
+            let msg = format!("edit range {:?} is not a valid range of the {}-byte source", range, self.source.len());
+            return Err(ParserError::IoError(Error::new(ErrorKind::InvalidInput, msg)));
+        }
+        let mut source = String::with_capacity(self.source.len() - range.len() + edit.replacement.len());
+        source.push_str(&self.source[..range.start]);
+        source.push_str(&edit.replacement);
+        source.push_str(&self.source[range.end..]);
+        if self.whole {
+            let (blocks, html) = parse_all(&source, self.options)?;
+            let change = Change {
+                old_blocks: 0..self.blocks.len(),
+                blocks: 0..blocks.len(),
+                old_output: 0..self.html.iter().map(String::len).sum(),
+                output: html.concat(),
+            };
+            self.source = source;
+            self.blocks = blocks;
+            self.html = html;
+            return Ok(change);
+        }
+        let delta = source.len() as isize - self.source.len() as isize;
+        let new_end = range.start + edit.replacement.len();
+
+        // 编辑之前的块可能与被编辑的块合并，多退一个块
+        let mut first = self.blocks.partition_point(|b| b.span.end.offset < range.start);
+        first = first.saturating_sub(1);
+        // 与前一个块共用一行的块要一起重新解析
+        while first > 0
+            && (self.restart(&source, first).0 > range.start
+                || self.blocks[first - 1].span.end.line >= self.blocks[first].span.start.line)
+        {
+            first -= 1;
+        }
+        let (start, start_pos) = self.restart(&source, first);
+
+        // 直接借用编辑后的文本分词，每交入一个 token 检查一次完成的块
+        let mut parser = ParserImpl::new(io::empty());
+        parser.set_position(start_pos);
+        parser.begin_tokens(self.options);
+        let mut tokens = StrTokenizer::new(&source[start..]);
+        let mut new_blocks = Vec::new();
+        let mut last = self.blocks.len();
+        let mut shift = None;
+        'parse: loop {
+            let token = tokens.next();
+            match token {
+                Some(token) => parser.push_token(token, self.options),
+                None => parser.end_input(self.options),
+            }
+            for block in parser.take_blocks() {
+                if block.span.start.offset >= new_end {
+                    if let Some((k, found)) = self.matching(&block, range.end, delta) {
+                        last = k;
+                        shift = Some(found);
+                        break 'parse;
+                    }
+                }
+                new_blocks.push(block);
+            }
+            if token.is_none() {
+                break;
+            }
+        }
+        let html = new_blocks
+            .iter()
+            .map(|block| render_block(block, self.options))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // 解析和输出都成功后才修改文档，沿用的块按编辑平移位置
+        if let Some(mut shift) = shift {
+            for block in &mut self.blocks[last..] {
+                shift.visit_block_mut(block);
+            }
+        }
+        let output_start: usize = self.html[..first].iter().map(String::len).sum();
+        let old_output_len: usize = self.html[first..last].iter().map(String::len).sum();
+        let count = new_blocks.len();
+        let output = html.concat();
+        self.source = source;
+        self.blocks.splice(first..last, new_blocks);
+        self.html.splice(first..last, html);
+        Ok(Change {
+            old_blocks: first..last,
+            blocks: first..first + count,
+            old_output: output_start..output_start + old_output_len,
+            output,
+        })
+    }
+
+    // 第 `index` 个块之前重新开始解析的位置：上一个块最后一行的下一行行首，按编辑后的文本 `source` 计算
+    fn restart(&self, source: &str, index: usize) -> (usize, Position) {
+        let Some(prev) = index.checked_sub(1).map(|i| &self.blocks[i]) else {
+            return (0, Position::default());
+        };
+        let end = prev.span.end;
+        let ending = match source.as_bytes().get(end.offset..) {
+            Some([b'\r', b'\n', ..]) => 2,
+            Some([b'\r' | b'\n', ..]) => 1,
+            _ => 0,
+        };
+        let pos = Position {
+            offset: end.offset + ending,
+            line: end.line + 1,
+            ..Position::default()
+        };
+        (pos.offset, pos)
+    }
+
+// 编辑位置之后与 `block` 相同的旧块，以及把旧块平移到新位置的偏移
+    fn matching(&self, block: &Block, old_end: usize, delta: isize) -> Option<(usize, Shift)> {
+        let start = block.span.start.offset as isize - delta;
+        let k = self.blocks.partition_point(|b| (b.span.start.offset as isize) < start);
+        let old = self
+            .blocks
+            .get(k)
+            .filter(|b| b.span.start.offset as isize == start && b.span.start.offset >= old_end)?;
+        let mut shift = Shift {
+            offset: delta,
+            line: block.span.start.line as i64 - old.span.start.line as i64,
+        };
+        let mut shifted = old.clone();
+        shift.visit_block_mut(&mut shifted);
+        (shifted == *block).then_some((k, shift))
+    }
+}
+
+// 完整解析 `source`，返回顶层块和输出；需要完整解析时输出只有一项，为整篇输出
+fn parse_all(source: &str, options: &Options) -> Result<(Vec<Block>, Vec<String>), ParserError> {
+    let doc = StrParser::new(source).parse_to_ast(options)?;
+    let html = if needs_full_parse(options) {
+        let mut out = Vec::new();
+        doc.render(Mapper::new(HtmlSchema::new(), &mut out), options)
+            .map_err(ParserError::IoError)?;
+        vec![String::from_utf8_lossy(&out).into_owned()]
+    } else {
+        doc.blocks
+            .iter()
+            .map(|block| render_block(block, options))
+            .collect::<Result<_, _>>()?
+    };
+    Ok((doc.blocks, html))
+}
+
+// 沿用的块的输出不随位置平移，输出 `data-sourcepos` 时也要完整解析
+fn needs_full_parse(options: &Options) -> bool {
+    options.needs_document_pass()
+        || options.source_positions
+        || options.toc.is_some()
+        || options.heading_ids.is_some()
+        || options.heading_numbers.is_some()
+        || options.variables.is_some()
+}
+
+fn render_block(block: &Block, options: &Options) -> Result<String, ParserError> {
+    let mut out = Vec::new();
+    let mut mapper = Mapper::new(HtmlSchema::new(), &mut out);
+    mapper.apply_options(options);
+    ast::render_blocks(std::slice::from_ref(block), &mut mapper, options)
+        .and_then(|_| mapper.flush())
+        .map_err(ParserError::IoError)?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+// 平移节点的位置，编辑之后的块只有偏移和行号变化
+struct Shift {
+    offset: isize,
+    line: i64,
+}
+
+impl Shift {
+    fn position(&self, pos: &mut Position) {
+        pos.offset = (pos.offset as isize + self.offset) as usize;
+        pos.line = (pos.line as i64 + self.line) as u32;
+    }
+}
+
+impl VisitMut for Shift {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        self.position(&mut block.span.start);
+        self.position(&mut block.span.end);
+        visit::walk_block_mut(self, block);
+    }
+
+    fn visit_inline_mut(&mut self, inline: &mut Inline) {
+        self.position(&mut inline.span.start);
+        self.position(&mut inline.span.end);
+        visit::walk_inline_mut(self, inline);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::MathMode, parser::tests::render_with};
+
+    // 每次编辑把最后一次出现的 `target` 替换为 `replacement`
+    fn check(src: &str, edits: &[(&str, &str)], options: &Options) {
+        let mut doc = IncrementalDocument::new(src, options).unwrap();
+        for (target, replacement) in edits {
+            let start = doc.source().rfind(target).unwrap();
+            let range = start..start + target.len();
+            let old_output = doc.output();
+            let change = doc
+                .edit(Edit {
+                    range: range.clone(),
+                    replacement: replacement.to_string(),
+                })
+                .unwrap();
+            let fresh = IncrementalDocument::new(doc.source(), options).unwrap();
+            assert_eq!(doc.blocks(), fresh.blocks(), "after editing {:?} to {:?}", range, replacement);
+            assert_eq!(doc.output(), fresh.output());
+
+            assert_eq!(doc.output(), render_with(ParserImpl::new(doc.source().as_bytes()), options));
+
+            let mut spliced = old_output;
+            spliced.replace_range(change.old_output, &change.output);
+            assert_eq!(spliced, doc.output());
+        }
+    }
+
+    #[test]
+    fn edits_match_full_parse() {
+        let src = "# 标题\n\n第一段\n第二行\n\n第三段\n\n> [!NOTE]\n> 提示\n\n```\ncode\n```\n\n$$\nx\n$$\n\n最后 *一段*\n";
+        let edits = [
+            ("标题", "新标题"),
+            // 删掉空行，两段合并
+            ("第二行\n\n第三段", "第二行\n第三段"),
+            // 打开一个没有闭合的代码块，之后的内容都在其中
+            ("第一段", "```\n第一段"),
+            ("```\n第一段", "第一段"),
+            ("\n> 提示", "\n> 提示\n正文"),
+            ("*\n", "*\r\n追加\r\n"),
+        ];
+        // 删掉 `!!!` 后提示框不再成立，其后的空行不能沿用
+        check("!!! tip\n    body\n\n\nafter", &[("tip", "xx")], &Options::default());
+        for (soft_breaks, source_positions) in [(true, false), (false, false), (false, true)] {
+            let options = Options {
+                math: MathMode::Passthrough,
+                soft_breaks,
+                source_positions,
+                ..Options::default()
+            };
+            check(src, &edits, &options);
+        }
+    }
+
+    #[test]
+    fn reparses_only_affected_blocks() {
+        let options = Options {
+            soft_breaks: true,
+            ..Options::default()
+        };
+        let src = "# 一\n\n段落一\n\n# 二\n\n段落二\n\n# 三\n";
+        let mut doc = IncrementalDocument::new(src, &options).unwrap();
+        let at = src.find("段落二").unwrap();
+        let change = doc
+            .edit(Edit {
+                range: at..at + "段落二".len(),
+                replacement: "*改*".to_string(),
+            })
+            .unwrap();
+        assert_eq!(change.output, "<h1>二</h1><br><p><em>改</em></p>");
+        assert_eq!(change.old_blocks, change.blocks);
+        assert_eq!(doc.blocks().last().map(|b| b.span.start.line), Some(9));
+
+        // 越界、反向或切在字符中间的范围返回错误，文档不变
+        let source = doc.source().to_string();
+        let output = doc.output();
+        for range in [0..source.len() + 1, Range { start: 3, end: 2 }, 3..4] {
+            let edit = Edit {
+                range,
+                replacement: "x".to_string(),
+            };
+            assert!(matches!(doc.edit(edit), Err(ParserError::IoError(e)) if e.kind() == ErrorKind::InvalidInput));
+        }
+        assert_eq!(doc.source(), source);
+        assert_eq!(doc.output(), output);
+    }
+}
@@ -0,0 +1,755 @@
+//! 行内规则：把一行内容切分成文本和各类行内片段，再解析为文档树的行内节点。
+//!
+//! 代码、公式等片段一旦识别出来就整体成为一个节点，其中的内容不会再经过其它行内规则。
+use std::{borrow::Cow, cell::Cell, ops::Range};
+
+use u8unit::Position;
+
+use crate::{
+    ast::{self, DirectiveNode, InlineKind, Span},
+    attributes::{self, Attributes},
+    autolink::{self, Reference},
+    cjk,
+    directive::{self, DirectiveSpec},
+    emoji,
+    error_handler::Diagnostic,
+    include::LineMap,
+    math::MathKind, options::Options,
+    ruby::{self, Ruby},
+    slug, units,
+    variables::{self, VariableOptions},
+    wiki::{self, WikiLink, WikiResolver},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Inline<'a> {
+    Text(&'a str),
+    /// `` `code` ``
+    Code(&'a str),
+    /// `#123`、`@user` 等引用
+    Autolink(Reference<'a>),
+    /// `*em*`、`**strong**`、`***both***`，以及对应的 `_` 形式
+    Emphasis(u8, &'a str),
+    Math(MathKind, &'a str),
+    Directive(DirectiveSpec<'a>),
+    /// `:smile:`，短代码名和对应的 emoji
+    Emoji(&'static str, &'static str),
+    /// `[text](dest "title"){attrs}`
+    Link(Link<'a>),
+    /// `![alt](src "title"){attrs}`
+    Image(Link<'a>),
+    /// `{漢字|かん|じ}`、`[漢字]^(kanji)`
+    Ruby(Ruby<'a>),
+    /// `[[Page#Heading|label]]`
+    WikiLink(WikiLink<'a>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Link<'a> {
+    pub text: &'a str,
+    pub dest: &'a str,
+    pub title: Option<&'a str>,
+    pub attrs: Attributes,
+}
+
+pub fn scan<'a>(line: &'a str, options: &Options) -> Vec<Inline<'a>> {
+    scan_ranges(line, options).into_iter().map(|(span, _)| span).collect()
+}
+
+/// 同 `scan`，另外给出每个片段在 `line` 中的范围
+pub fn scan_ranges<'a>(line: &'a str, options: &Options) -> Vec<(Inline<'a>, Range<usize>)> {
+    let bytes = line.as_bytes();
+    let mut spans = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < line.len() {
+        if bytes[i] == b'`' {
+            let (code, end) = scan_code(line, i);
+            if let Some(code) = code {
+                push_text(&mut spans, line, text_start..i);
+                spans.push((Inline::Code(code), i..end));
+                text_start = end;
+            }
+            i = end;
+            continue;
+        }
+        if options.math.is_enabled() {
+            // `\$` 输出字面量 `$`
+            if line[i..].starts_with("\\$") {
+                push_text(&mut spans, line, text_start..i);
+                text_start = i + 1;
+                i += 2;
+                continue;
+            }
+            if let Some((span, end)) = scan_math(line, i) {
+                push_text(&mut spans, line, text_start..i);
+                spans.push((span, i..end));
+                i = end;
+                text_start = end;
+                continue;
+            }
+        }
+        if !options.directives.is_empty() && bytes[i] == b':' {
+            if let Some((spec, end)) = scan_directive(line, i, options) {
+                push_text(&mut spans, line, text_start..i);
+                spans.push((Inline::Directive(spec), i..end));
+                i = end;
+                text_start = end;
+                continue;
+            }
+        }
+        if options.ruby && (bytes[i] == b'{' || bytes[i] == b'[') {
+            let found = if bytes[i] == b'{' {
+                ruby::scan_braces(line, i)
+            } else {
+                ruby::scan_caret(line, i)
+            };
+            if let Some((ruby, end)) = found {
+                push_text(&mut spans, line, text_start..i);
+                spans.push((Inline::Ruby(ruby), i..end));
+                i = end;
+                text_start = end;
+                continue;
+            }
+        }
+        if options.wiki_resolver.is_some() && bytes[i] == b'[' {
+            if let Some((link, end)) = wiki::parse(line, i) {
+                push_text(&mut spans, line, text_start..i);
+                spans.push((Inline::WikiLink(link), i..end));
+                i = end;
+                text_start = end;
+                continue;
+            }
+        }
+        if options.emoji.is_enabled() && bytes[i] == b':' {
+            if let Some((name, emoji, end)) = emoji::scan(line, i) {
+                push_text(&mut spans, line, text_start..i);
+                spans.push((Inline::Emoji(name, emoji), i..end));
+                i = end;
+                text_start = end;
+                continue;
+            }
+        }
+        if bytes[i] == b'*' || bytes[i] == b'_' {
+            if let Some((span, end)) = scan_emphasis(line, i, options) {
+                push_text(&mut spans, line, text_start..i);
+                spans.push((span, i..end));
+                i = end;
+                text_start = end;
+            } else {
+                // 整串符号一起跳过，不从中间重新开始匹配
+                i += units::count_leading(&line[i..], bytes[i]);
+            }
+            continue;
+        }
+        if let Some(autolink) = &options.autolink {
+            if let Some((start, reference, end)) = autolink::scan(line, i, text_start, autolink) {
+                push_text(&mut spans, line, text_start..start);
+                spans.push((Inline::Autolink(reference), start..end));
+                i = end;
+                text_start = end;
+                continue;
+            }
+        }
+        let image = line[i..].starts_with("![");
+        if image || bytes[i] == b'[' {
+            let open = if image { i + 1 } else { i };
+            if let Some((link, end)) = scan_link(line, open, options.variables.is_some()) {
+                push_text(&mut spans, line, text_start..i);
+                spans.push((if image { Inline::Image(link) } else { Inline::Link(link) }, i..end));
+                i = end;
+                text_start = end;
+                continue;
+            }
+        }
+        i = units::next_char(line, i);
+    }
+    push_text(&mut spans, line, text_start..line.len());
+    spans
+}
+
+/// 行内文本中的下标到源文件位置的对应。文本由若干段在源文件中连续的字符拼成，
+/// 例如软换行合并的段落每行一段，每段记下开头的下标和位置，其余位置从段首数出来
+#[derive(Debug, Default)]
+pub(crate) struct Locator {
+    segments: Vec<(usize, Position)>,
+    // 上次算出的位置，按顺序查询时从这里接着数
+    last: Cell<Option<(usize, Position)>>,
+}
+
+impl Locator {
+    pub(crate) fn new(start: Position) -> Self {
+        let mut locator = Self::default();
+        locator.push(0, start);
+        locator
+    }
+
+    /// 文本从下标 `index` 开始的一段在源文件中从 `pos` 开始
+    pub(crate) fn push(&mut self, index: usize, pos: Position) {
+        self.segments.push((index, pos));
+    }
+
+    pub(crate) fn position(&self, text: &str, index: usize) -> Position {
+        let i = self.segments.partition_point(|(start, _)| *start <= index);
+        let Some(&(start, pos)) = i.checked_sub(1).and_then(|i| self.segments.get(i)) else {
+            return Position::default();
+        };
+        let (from, mut pos) = match self.last.get() {
+            Some((last, last_pos)) if last >= start && last <= index => (last, last_pos),
+            _ => (start, pos),
+        };
+        pos.advance_text(&text[from..index]);
+        self.last.set(Some((index, pos)));
+        pos
+    }
+}
+
+/// 把行内文本解析为文档树节点时的上下文：文本及其位置、变量和诊断信息
+pub(crate) struct InlineContext<'a> {
+    pub(crate) options: &'a Options,
+    pub(crate) text: &'a str,
+    pub(crate) locator: &'a Locator,
+    pub(crate) variables: Option<&'a VariableOptions>,
+    pub(crate) diagnostics: &'a mut Vec<Diagnostic>,
+    // 展开过 include 时，诊断信息的行号据此换回原文
+    pub(crate) line_map: Option<&'a LineMap>,
+    // 是否在链接文字中，链接不能嵌套
+    pub(crate) in_link: bool,
+}
+
+impl InlineContext<'_> {
+    fn span(&self, range: Range<usize>) -> Span {
+        Span::new(self.locator.position(self.text, range.start), self.locator.position(self.text, range.end))
+    }
+
+    /// 记录一条诊断信息，位置为 `pos`
+    pub(crate) fn report(&mut self, msg: String, pos: Position) {
+        let mut diagnostic = Diagnostic {
+            msg: msg.into(),
+            line_num: pos.line,
+            column_num: pos.column,
+            file: None,
+        };
+        if let Some(line_map) = self.line_map {
+            line_map.remap_diagnostic(&mut diagnostic);
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// 替换 `{{ name }}`，未开启时原样返回；未定义的变量报在 `pos` 处
+    pub(crate) fn expand<'t>(&mut self, text: &'t str, pos: Position) -> Cow<'t, str> {
+        let Some(variables) = self.variables else {
+            return Cow::Borrowed(text);
+        };
+        let mut undefined = Vec::new();
+        let text = variables::expand(text, variables, &mut undefined);
+        for name in undefined {
+            self.report(format!("undefined variable: {}", name), pos);
+        }
+        text
+    }
+
+    /// 解析 `text[range]` 中的行内内容
+    pub(crate) fn parse(&mut self, range: Range<usize>) -> Vec<ast::Inline> {
+        let options = self.options;
+        let line = &self.text[range.clone()];
+        let mut inlines = Vec::new();
+        for (span, r) in scan_ranges(line, options) {
+            let r = range.start + r.start..range.start + r.end;
+            let node_span = self.span(r.clone());
+            let kind = match span {
+                Inline::Text(text) => {
+                    let text = self.expand(text, node_span.start);
+                    match &options.cjk {
+                        Some(cjk) if cjk.pangu => InlineKind::Text(cjk::pangu(&text)),
+                        _ => InlineKind::Text(text.into_owned()),
+                    }
+                }
+                Inline::Emphasis(level, content) => {
+                    let start = r.start + level as usize;
+                    InlineKind::Emphasis {
+                        level,
+                        children: self.parse(start..start + content.len()),
+                    }
+                }
+                Inline::Code(code) if self.variables.is_some_and(|v| v.in_code) => {
+                    InlineKind::Code(self.expand(code, node_span.start).into_owned())
+                }
+                Inline::Code(code) => InlineKind::Code(code.to_string()),
+                Inline::Math(kind, tex) => InlineKind::Math {
+                    kind,
+                    tex: tex.to_string(),
+                },
+                Inline::Directive(spec) => {
+                    // `:name[label]`，label 从冒号之后的 `name[` 后开始
+                    let children = match spec.label {
+                        Some(label) => {
+                            let start = r.start + 1 + spec.label_offset();
+                            self.parse(start..start + label.len())
+                        }
+                        None => Vec::new(),
+                    };
+                    InlineKind::Directive(directive_node(spec, children))
+                }
+                Inline::Emoji(name, emoji) => InlineKind::Emoji {
+                    name: name.to_string(),
+                    emoji: emoji.to_string(),
+                },
+                Inline::Link(link) => {
+                    let dest = self.expand(link.dest, node_span.start).into_owned();
+                    let in_link = std::mem::replace(&mut self.in_link, true);
+                    let children = self.parse(r.start + 1..r.start + 1 + link.text.len());
+                    self.in_link = in_link;
+                    InlineKind::Link {
+                        dest,
+                        title: link.title.map(String::from),
+                        attrs: link.attrs,
+                        children,
+                    }
+                }
+                Inline::Image(image) => InlineKind::Image {
+                    src: self.expand(image.dest, node_span.start).into_owned(),
+                    alt: image.text.to_string(),
+                    title: image.title.map(String::from),
+                    attrs: image.attrs,
+                },
+                Inline::Ruby(ruby) => InlineKind::Ruby(
+                    ruby.groups
+                        .iter()
+                        .map(|(base, text)| (base.to_string(), text.to_string()))
+                        .collect(),
+                ),
+                Inline::Autolink(reference) => {
+                    let url = options.autolink.as_ref().and_then(|a| reference.url(a));
+                    let text = InlineKind::Text(reference.text());
+                    match url {
+                        // 已有链接的文字中不再生成链接
+                        Some(url) if !self.in_link => InlineKind::Link {
+                            dest: url,
+                            title: None,
+                            attrs: Attributes::default(),
+                            children: vec![ast::Inline { kind: text, span: node_span }],
+                        },
+                        _ => text,
+                    }
+                }
+                Inline::WikiLink(link) => match &options.wiki_resolver {
+                    Some(resolver) => self.wiki_link(&link, resolver.as_ref(), node_span),
+                    None => continue,
+                },
+            };
+            inlines.push(ast::Inline { kind, span: node_span });
+        }
+        if options.cjk.as_ref().is_some_and(|cjk| cjk.pangu) {
+            pangu_between_nodes(&mut inlines);
+        }
+        inlines
+    }
+
+    // 页面不存在时生成 `class="missing"` 的链接并记录诊断信息
+    fn wiki_link(&mut self, link: &WikiLink, resolver: &dyn WikiResolver, span: Span) -> InlineKind {
+        let page = link.page;
+        let mut attrs = Attributes::default();
+        let mut href = if page.is_empty() {
+            Some(String::new())
+        } else {
+            resolver.resolve(page)
+        };
+        if href.is_none() {
+            attrs.classes.push("missing".to_string());
+            self.report(format!("wiki page not found: {}", page), span.start);
+        }
+        if let (Some(href), Some(heading)) = (&mut href, link.heading) {
+            href.push('#');
+            // 与标题 id 使用同一种 slug 算法
+            let algorithm = self.options.heading_ids.as_ref().map(|ids| ids.slug).unwrap_or_default();
+            href.push_str(&slug::slugify(heading, algorithm));
+        }
+        InlineKind::Link {
+            dest: href.unwrap_or_else(|| "#".to_string()),
+            title: None,
+            attrs,
+            children: vec![ast::Inline {
+                kind: InlineKind::Text(link.label.to_string()),
+                span,
+            }],
+        }
+    }
+}
+
+// 盘古之白也加在相邻的节点之间，例如 `用**Rust**写`：空格优先放进两侧的文本节点，
+// 都不是文本时插入一个只有空格的文本节点。节点中的内容在解析子节点时已经处理过
+fn pangu_between_nodes(inlines: &mut Vec<ast::Inline>) {
+    let mut i = 1;
+    while i < inlines.len() {
+        let prev = boundary_char(&inlines[i - 1], false);
+        let next = boundary_char(&inlines[i], true);
+        if prev.zip(next).is_some_and(|(prev, next)| cjk::pangu_between(prev, next)) {
+            let (before, after) = inlines.split_at_mut(i);
+            match (&mut before[i - 1].kind, &mut after[0].kind) {
+                (InlineKind::Text(text), _) => text.push(' '),
+                (_, InlineKind::Text(text)) => text.insert(0, ' '),
+                _ => {
+                    let start = inlines[i].span.start;
+                    let space = ast::Inline {
+                        kind: InlineKind::Text(" ".to_string()),
+                        span: Span::new(start, start),
+                    };
+                    inlines.insert(i, space);
+                    i += 1;
+                }
+            }
+        }
+        i += 1;
+    }
+}
+
+// 节点输出的第一个（`first`）或最后一个字符；公式、图片等不参与盘古之白
+fn boundary_char(inline: &ast::Inline, first: bool) -> Option<char> {
+    let edge = |text: &str| if first { text.chars().next() } else { text.chars().next_back() };
+    match &inline.kind {
+        InlineKind::Text(text) | InlineKind::Code(text) => edge(text),
+        InlineKind::Emphasis { children, .. } | InlineKind::Link { children, .. } => {
+            let child = if first { children.first() } else { children.last() };
+            child.and_then(|child| boundary_char(child, first))
+        }
+        _ => None,
+    }
+}
+
+/// 指令节点，`children` 为已经解析好的 label 或容器中的块
+pub(crate) fn directive_node<C>(spec: DirectiveSpec, children: Vec<C>) -> DirectiveNode<C> {
+    DirectiveNode {
+        name: spec.name,
+        label: spec.label.map(String::from),
+        attributes: spec.attributes,
+        children,
+    }
+}
+
+fn push_text<'a>(spans: &mut Vec<(Inline<'a>, Range<usize>)>, line: &'a str, range: Range<usize>) {
+    if !range.is_empty() {
+        spans.push((Inline::Text(&line[range.clone()]), range));
+    }
+}
+
+// 反引号串后面找长度相同的反引号串，找不到时整串反引号按文本处理，返回结束位置
+fn scan_code(line: &str, i: usize) -> (Option<&str>, usize) {
+    let ticks = units::count_leading(&line[i..], b'`');
+    let mut j = i + ticks;
+    while let Some(found) = units::find(line, "`", j) {
+        j = found;
+        let run = units::count_leading(&line[j..], b'`');
+        if run == ticks {
+            let code = &line[i + ticks..j];
+            // 两端各有一个空格时去掉，用于 `` ` `` 这类以反引号开头的代码
+            let code = match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                Some(inner) if !units::is_blank(code) => inner,
+                _ => code,
+            };
+            return (Some(code), j + run);
+        }
+        j += run;
+    }
+    (None, i + ticks)
+}
+
+// 找与开头同样长度的一串符号作为结束，其间的代码不参与匹配
+fn scan_emphasis<'a>(line: &'a str, i: usize, options: &Options) -> Option<(Inline<'a>, usize)> {
+    let bytes = line.as_bytes();
+    let marker = bytes[i];
+    let run = units::count_leading(&line[i..], marker);
+    let cjk = options.cjk.is_some();
+    let prev = |j: usize| units::char_before(line, j);
+    let next = |j: usize| units::char_at(line, j);
+    if run > 3 || !can_open(marker, prev(i), next(i + run), cjk) {
+        return None;
+    }
+    let mut j = i + run;
+    while j < line.len() {
+        if bytes[j] == b'`' {
+            j = scan_code(line, j).1;
+            continue;
+        }
+        if bytes[j] == marker {
+            let len = units::count_leading(&line[j..], marker);
+            if len == run && j > i + run && can_close(marker, prev(j), next(j + len), cjk) {
+                return Some((Inline::Emphasis(run as u8, &line[i + run..j]), j + len));
+            }
+            j += len;
+            continue;
+        }
+        j += 1;
+    }
+    None
+}
+
+fn is_space(c: Option<char>) -> bool {
+    c.is_none_or(char::is_whitespace)
+}
+
+fn is_punctuation(c: Option<char>) -> bool {
+    c.is_some_and(|c| !c.is_alphanumeric() && !c.is_whitespace())
+}
+
+// CommonMark 的 left-flanking 规则；中日韩模式下外侧紧挨中日韩文字时按标点处理，
+// 这样 `的**「中文」**的` 这类写法也能识别
+fn left_flanking(prev: Option<char>, next: Option<char>, cjk: bool) -> bool {
+    let outer = is_space(prev) || is_punctuation(prev) || (cjk && prev.is_some_and(cjk::is_cjk));
+    !is_space(next) && (!is_punctuation(next) || outer)
+}
+
+fn right_flanking(prev: Option<char>, next: Option<char>, cjk: bool) -> bool {
+    let outer = is_space(next) || is_punctuation(next) || (cjk && next.is_some_and(cjk::is_cjk));
+    !is_space(prev) && (!is_punctuation(prev) || outer)
+}
+
+// `_` 不能用于单词内部的强调，例如 `snake_case_name`
+fn can_open(marker: u8, prev: Option<char>, next: Option<char>, cjk: bool) -> bool {
+    let left = left_flanking(prev, next, cjk);
+    if marker == b'*' {
+        return left;
+    }
+    left && (!right_flanking(prev, next, cjk) || is_punctuation(prev))
+}
+
+fn can_close(marker: u8, prev: Option<char>, next: Option<char>, cjk: bool) -> bool {
+    let right = right_flanking(prev, next, cjk);
+    if marker == b'*' {
+        return right;
+    }
+    right && (!left_flanking(prev, next, cjk) || is_punctuation(next))
+}
+
+fn scan_math(line: &str, i: usize) -> Option<(Inline<'_>, usize)> {
+    let bytes = line.as_bytes();
+    let rest = &line[i..];
+    if rest.starts_with("\\(") {
+        let end = units::find(line, "\\)", i + 2)?;
+        return Some((Inline::Math(MathKind::Inline, &line[i + 2..end]), end + 2));
+    }
+    if rest.starts_with("\\[") {
+        let end = units::find(line, "\\]", i + 2)?;
+        return Some((Inline::Math(MathKind::Display, &line[i + 2..end]), end + 2));
+    }
+    if rest.starts_with("$$") {
+        let end = units::find(line, "$$", i + 2).filter(|&end| end > i + 2)?;
+        return Some((Inline::Math(MathKind::Display, &line[i + 2..end]), end + 2));
+    }
+    if bytes[i] != b'$' {
+        return None;
+    }
+    // `$` 后必须紧跟非空白字符，结束的 `$` 前不能是空白、后面不能紧跟数字，
+    // 这样 `$5 和 $6` 这类金额不会被当成公式
+    if bytes.get(i + 1).is_none_or(|b| units::is_blank_byte(*b)) {
+        return None;
+    }
+    let mut j = units::next_char(line, i + 1);
+    while let Some(found) = units::find(line, "$", j) {
+        j = found;
+        let prev = bytes[j - 1];
+        let next_is_digit = bytes.get(j + 1).is_some_and(u8::is_ascii_digit);
+        if !units::is_blank_byte(prev) && prev != b'\\' && !next_is_digit {
+            return Some((Inline::Math(MathKind::Inline, &line[i + 1..j]), j + 1));
+        }
+        j += 1;
+    }
+    None
+}
+
+// `open` 处是 `[`：`[text](dest "title")`，后面可以跟 `{attrs}`；
+// 开启变量替换时地址中可以有带空格的 `{{ name }}`，输出时再替换
+fn scan_link(line: &str, open: usize, variables: bool) -> Option<(Link<'_>, usize)> {
+    let bytes = line.as_bytes();
+    let close = units::bracket_end(line, open)?;
+    if bytes.get(close + 1) != Some(&b'(') {
+        return None;
+    }
+    let skip_blank = |mut j: usize| {
+        while bytes.get(j).is_some_and(|b| units::is_blank_byte(*b)) {
+            j += 1;
+        }
+        j
+    };
+    let mut j = skip_blank(close + 2);
+    let dest = if bytes.get(j) == Some(&b'<') {
+        let end = j + 1 + line[j + 1..].find('>')?;
+        let dest = &line[j + 1..end];
+        j = end + 1;
+        dest
+    } else {
+        // 允许成对的括号，例如维基百科的链接
+        let start = j;
+        let mut depth = 0;
+        while let Some(&b) = bytes.get(j) {
+            if let Some((_, end)) = variables.then(|| variables::find(line, j)).flatten() {
+                j = end;
+                continue;
+            }
+            if units::is_blank_byte(b) || (b == b')' && depth == 0) {
+                break;
+            }
+            if b == b'(' {
+                depth += 1;
+            } else if b == b')' {
+                depth -= 1;
+            }
+            j = units::next_char(line, j);
+        }
+        &line[start..j]
+    };
+    j = skip_blank(j);
+    let mut title = None;
+    if let Some(&quote) = bytes.get(j).filter(|b| matches!(b, b'"' | b'\'' | b'(')) {
+        let close_quote = if quote == b'(' { ')' } else { quote as char };
+        let end = j + 1 + line[j + 1..].find(close_quote)?;
+        title = Some(&line[j + 1..end]);
+        j = skip_blank(end + 1);
+    }
+    if bytes.get(j) != Some(&b')') {
+        return None;
+    }
+    let mut end = j + 1;
+    let mut attrs = Attributes::default();
+    if let Some((parsed, len)) = attributes::parse(&line[end..]) {
+        attrs = parsed;
+        end += len;
+    }
+    let link = Link {
+        text: &line[open + 1..close],
+        dest,
+        title,
+        attrs,
+    };
+    Some((link, end))
+}
+
+// `:name[label]{attrs}`，冒号前不能是字母数字，避免 `a:b`、`http://` 之类的文本
+fn scan_directive<'a>(line: &'a str, i: usize, options: &Options) -> Option<(DirectiveSpec<'a>, usize)> {
+    if units::char_before(line, i).is_some_and(char::is_alphanumeric) {
+        return None;
+    }
+    let (spec, len) = directive::parse_spec(&line[i + 1..], true)?;
+    options.directives.get(&spec.name)?;
+    Some((spec, i + 1 + len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        options::{EmojiMode, MathMode},
+        parser::{tests::render_with, ParserImpl},
+    };
+
+    // 一行文本按行内规则输出，去掉外面的段落标签
+    fn render_line(src: &str, options: &Options) -> String {
+        let html = render_with(ParserImpl::new(src.as_bytes()), options);
+        html.strip_prefix("<p>").and_then(|html| html.strip_suffix("</p>")).unwrap_or(&html).to_string()
+    }
+
+    fn math_options() -> Options {
+        Options {
+            math: MathMode::Passthrough,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn math_spans_are_shielded() {
+        let line = "a $x_1$ and $$y$$ \\(z\\)";
+        let spans = scan(line, &math_options());
+        assert_eq!(spans.len(), 6);
+        assert!(matches!(spans[1], Inline::Math(MathKind::Inline, tex) if tex == "x_1"));
+        assert!(matches!(spans[3], Inline::Math(MathKind::Display, tex) if tex == "y"));
+        assert!(matches!(spans[5], Inline::Math(MathKind::Inline, tex) if tex == "z"));
+    }
+
+    #[test]
+    fn links_and_images_with_attributes() {
+        let line = "see [the *docs*](https://a.b/c_(d) \"T\"){.ext} ![logo](<a b.png>){width=20} [no link]";
+        let spans = scan(line, &Options::default());
+        assert_eq!(spans.len(), 5);
+        let Inline::Link(link) = &spans[1] else { panic!() };
+        assert_eq!(link.text, "the *docs*");
+        assert_eq!(link.dest, "https://a.b/c_(d)");
+        assert_eq!(link.title, Some("T"));
+        assert_eq!(link.attrs.classes, vec!["ext"]);
+        let Inline::Image(image) = &spans[3] else { panic!() };
+        assert_eq!(image.dest, "a b.png");
+        assert_eq!(image.attrs.get("width"), Some("20"));
+        assert!(matches!(spans[4], Inline::Text(_)));
+    }
+
+    #[test]
+    fn code_spans_shield_other_rules() {
+        let line = "`$x$` and ``a ` b`` ``` ` x";
+        let spans = scan(line, &math_options());
+        assert_eq!(spans.len(), 4);
+        assert!(matches!(spans[0], Inline::Code(code) if code == "$x$"));
+        assert!(matches!(spans[2], Inline::Code(code) if code == "a ` b"));
+        assert!(matches!(spans[3], Inline::Text(text) if text == " ``` ` x"));
+    }
+
+    #[test]
+    fn emoji_shortcodes() {
+        let options = Options {
+            emoji: EmojiMode::Image("/e/{code}.png".to_string()),
+            ..Options::default()
+        };
+        let line = "ok :+1: `:+1:` :nope: 10:30:00";
+        let spans = scan(line, &options);
+        assert_eq!(spans[1], Inline::Emoji("+1", "👍"));
+        assert!(matches!(spans[3], Inline::Code(_)));
+        assert!(matches!(spans[4], Inline::Text(text) if text == " :nope: 10:30:00"));
+        assert_eq!(scan(line, &Options::default()).len(), 3);
+    }
+
+    #[test]
+    fn ruby_annotations() {
+        let options = Options {
+            ruby: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            render_line("{漢字|かん|じ}と[東京]^(とうきょう)", &options),
+            "<ruby><rb>漢</rb><rt>かん</rt><rb>字</rb><rt>じ</rt></ruby>と\
+             <ruby><rb>東京</rb><rt>とうきょう</rt></ruby>"
+        );
+
+        // 只有注音的一行是段落，不是属性行
+        assert_eq!(
+            render_with(ParserImpl::new("{漢字|かん|じ}\n\n正文".as_bytes()), &options),
+            "<p><ruby><rb>漢</rb><rt>かん</rt><rb>字</rb><rt>じ</rt></ruby></p><br><p>正文</p>"
+        );
+    }
+
+    #[test]
+    fn emphasis_flanking() {
+        let render = render_line;
+        let plain = Options::default();
+        assert_eq!(render("*a* __b__ ***c*** snake_case_name 2 * 3 * 4", &plain),
+            "<em>a</em> <strong>b</strong> <em><strong>c</strong></em> snake_case_name 2 * 3 * 4");
+        assert_eq!(render("**`*`**", &plain), "<strong><code>*</code></strong>");
+
+        let src = "这是**「重点」**的内容";
+        assert_eq!(render(src, &plain), src);
+        let cjk = Options {
+            cjk: Some(cjk::CjkOptions { pangu: true }),
+            ..Options::default()
+        };
+        assert_eq!(render(src, &cjk), "这是<strong>「重点」</strong>的内容");
+        assert_eq!(render("用**Rust**写", &cjk), "用 <strong>Rust</strong> 写");
+        assert_eq!(render("**中文**`code`和[链接](/a)2个", &cjk),
+            "<strong>中文</strong> <code>code</code> 和<a href=\"/a\">链接</a> 2 个");
+    }
+
+    #[test]
+    fn dollar_amounts_are_text() {
+        let line = "costs $5 or $6, \\$x";
+        let spans = scan(line, &math_options());
+        assert!(spans.iter().all(|s| matches!(s, Inline::Text(_))));
+        let off = scan(line, &Options::default());
+        assert_eq!(off, vec![Inline::Text(line)]);
+    }
+}
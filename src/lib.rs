@@ -7,5 +7,32 @@ pub mod schema;
 pub mod error_handler;
 pub mod mapper;
 pub mod engine;
+pub mod options;
+pub mod inline;
+pub mod block;
+pub mod attributes;
+pub mod directive;
+pub mod math;
+pub mod units;
+pub mod abbr;
+pub mod document;
+pub mod wiki;
+pub mod emoji;
+pub mod toc;
+pub mod slug;
+pub mod numbering;
+pub mod ruby;
+pub mod cjk;
+pub mod autolink;
+pub mod include;
+pub mod variables;
+pub mod ast;
+pub mod event;
+pub mod visit;
+pub mod cst;
+pub mod sourcemap;
+pub mod push;
+pub mod incremental;
+pub mod str_parser;
 
 pub type CowStr = Cow<'static, str>;
\ No newline at end of file
@@ -11,6 +11,25 @@ use mrakdown_engine::{
 fn main() {
     println!("Hello, world!");
     let mut engine = MrakdownEngine::new();
+    // `--var key=value` 定义 `{{ key }}` 变量，可以重复
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let var = match arg.strip_prefix("--var=") {
+            Some(var) => Some(var.to_string()),
+            None if arg == "--var" => args.next(),
+            None => {
+                eprintln!("unknown argument: {}", arg);
+                std::process::exit(2);
+            }
+        };
+        match var.as_deref().and_then(|var| var.split_once('=')) {
+            Some((key, value)) => engine.set_variable(key, value),
+            None => {
+                eprintln!("usage: --var key=value");
+                std::process::exit(2);
+            }
+        }
+    }
     let error_handler = ErrorHandlerImpl::new();
     let writer = WriterImpl::new(File::create("123.html").unwrap());
     let file = File::open("123.txt").unwrap();
@@ -1,10 +1,57 @@
 use u8unit::U8Unit;
 
-use crate::{schema::Schema, writer::Writer};
-use std::io::Result as IoResult;
+use crate::{
+    abbr::{self, Abbreviation},
+    ast::Span,
+    emoji,
+    attributes::Attributes,
+    directive::{Directive, DirectiveHandler, DirectiveKind}, math::{self, MathKind}, options::{EmojiMode, MathMode, Options},
+    schema::Schema,
+    sourcemap::{SourceMap, SourceMapping},
+    numbering::{HeadingNumbers, NumberingOptions},
+    options::HeadingIdOptions,
+    ruby::Ruby,
+    slug::{self, Slugger},
+    toc::{self, TocEntry, TocOptions},
+    units,
+    writer::Writer,
+};
+use std::{borrow::Cow, io::Result as IoResult};
 pub struct Mapper<S: Schema, W: Writer> {
+    #[allow(dead_code)]
     schema: S,
     writer: W,
+    // 暂存输出的缓冲区，例如指令的子内容需要整体交给处理函数
+    captures: Vec<Vec<u8>>,
+    // 整篇文档的缩写定义，正文中的文本经过 `write_html_text` 时替换
+    abbreviations: Vec<Abbreviation>,
+    // 当前嵌套在几层链接中，链接文字不做缩写替换
+    link_depth: usize,
+    // 开启目录后整篇文档先写入这里，`flush` 时插入目录再交给 writer
+    toc: Option<TocState>,
+    // 开启后为没有 id 的标题生成 id
+    heading_ids: Option<HeadingIdOptions>,
+    slugger: Slugger,
+    numbers: Option<HeadingNumbers>,
+    // 正在输出的标题：需要生成 id 或收入目录时，开始标签等到内容输出完才能确定
+    open_headings: Vec<(u8, Attributes)>,
+    // 正在输出的块在源文件中的范围
+    line_span: Span,
+    // 开启后在块元素上输出 `data-sourcepos`
+    source_positions: bool,
+    // 开启后记录每个块的输出范围；`open_blocks` 为尚未结束的块，在暂存区中开始的块记为 `None`
+    source_map: Option<Vec<SourceMapping>>,
+    open_blocks: Vec<Option<(usize, Span)>>,
+    // 已经交给 writer 的字节数
+    written: usize,
+}
+
+struct TocState {
+    options: TocOptions,
+    buffer: Vec<u8>,
+    // 目录在 `buffer` 中的插入位置
+    marks: Vec<usize>,
+    entries: Vec<TocEntry>,
 }
 
 impl<S: Schema, W: Writer> Mapper<S, W> {
@@ -13,101 +60,560 @@ impl<S: Schema, W: Writer> Mapper<S, W> {
         Self { 
             schema: s,
             writer: w,
+            captures: Vec::new(),
+            abbreviations: Vec::new(),
+            link_depth: 0,
+            toc: None,
+            heading_ids: None,
+            slugger: Slugger::default(),
+            numbers: None,
+            open_headings: Vec::new(),
+            line_span: Span::default(),
+            source_positions: false,
+            source_map: None,
+            open_blocks: Vec::new(),
+            written: 0,
         }
     }
 
+    fn write(&mut self, data: &[u8]) -> IoResult<usize> {
+        match self.captures.last_mut() {
+            Some(buf) => {
+                buf.extend_from_slice(data);
+                Ok(data.len())
+            }
+            None => match &mut self.toc {
+                Some(toc) => {
+                    toc.buffer.extend_from_slice(data);
+                    Ok(data.len())
+                }
+                None => {
+                    self.written += data.len();
+                    self.writer.write(data)
+                }
+            },
+        }
+    }
 
+    /// 按配置开启目录、标题 id、编号和 `data-sourcepos`
+    pub fn apply_options(&mut self, options: &Options) {
+        if let Some(toc) = &options.toc {
+            self.enable_toc(toc.clone());
+        }
+        if let Some(heading_ids) = &options.heading_ids {
+            self.enable_heading_ids(heading_ids.clone());
+        }
+        if let Some(numbering) = &options.heading_numbers {
+            self.enable_heading_numbers(numbering.clone());
+        }
+        self.source_positions = options.source_positions;
+    }
+
+    /// 记录每个块的输出范围，输出结束后用 `take_source_map` 取出
+    pub fn enable_source_map(&mut self) {
+        self.source_map = Some(Vec::new());
+    }
+
+    pub fn take_source_map(&mut self) -> SourceMap {
+        SourceMap::new(self.source_map.take().unwrap_or_default())
+    }
+
+    // 目前输出了多少字节，开启目录时为缓冲区的长度，插入目录后在 `flush` 中修正
+    fn output_len(&self) -> usize {
+        match &self.toc {
+            Some(toc) => toc.buffer.len(),
+            None => self.written,
+        }
+    }
+
+    // 块开始，记下输出位置和当前块的范围
+    fn open_block(&mut self) {
+        if self.source_map.is_some() {
+            let open = self.captures.is_empty().then(|| (self.output_len(), self.line_span));
+            self.open_blocks.push(open);
+        }
+    }
+
+    fn close_block(&mut self) {
+        let end = self.output_len();
+        if let (Some(map), Some(Some((start, source)))) = (&mut self.source_map, self.open_blocks.pop()) {
+            map.push(SourceMapping {
+                output: start..end,
+                source,
+            });
+        }
+    }
+
+    /// 块元素的属性，开启 `source_positions` 时加上当前块的 `data-sourcepos`
+    fn block_attrs<'a>(&self, attrs: &'a Attributes) -> Cow<'a, Attributes> {
+        if !self.source_positions {
+            return Cow::Borrowed(attrs);
+        }
+        let mut attrs = attrs.clone();
+        attrs.pairs.push(("data-sourcepos".to_string(), sourcepos(self.line_span)));
+        Cow::Owned(attrs)
+    }
+
+    pub fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    /// 接下来输出的块在源文件中的范围
+    pub fn set_line_span(&mut self, span: Span) {
+        self.line_span = span;
+    }
+
+    pub fn line_span(&self) -> Span {
+        self.line_span
+    }
+
+    /// 缓存之后的输出并收集标题，用于 `[TOC]`；目录要链接到标题，没有开启标题 id 时按默认规则生成
+    pub fn enable_toc(&mut self, options: TocOptions) {
+        self.heading_ids.get_or_insert_with(HeadingIdOptions::default);
+        let marks = if options.prepend { vec![0] } else { Vec::new() };
+        self.toc = Some(TocState {
+            options,
+            buffer: Vec::new(),
+            marks,
+            entries: Vec::new(),
+        });
+    }
+
+    /// 预先记录手写的标题 id，之后自动生成的 id 不与它们重复
+    pub fn claim_heading_ids<'a>(&mut self, ids: impl IntoIterator<Item = &'a str>) {
+        for id in ids {
+            self.slugger.claim(id);
+        }
+    }
+
+    pub fn enable_heading_ids(&mut self, options: HeadingIdOptions) {
+        self.heading_ids = Some(options);
+    }
+
+    pub fn enable_heading_numbers(&mut self, options: NumberingOptions) {
+        self.numbers = Some(HeadingNumbers::new(options));
+    }
+
+    /// 在当前位置插入目录，指令等暂存的内容中不支持
+    pub fn write_html_toc_marker(&mut self) -> IoResult<()> {
+        if let (Some(toc), true) = (&mut self.toc, self.captures.is_empty()) {
+            toc.marks.push(toc.buffer.len());
+        }
+        Ok(())
+    }
+
+    /// 之后的输出先写入缓冲区，直到对应的 `end_capture`
+    pub fn begin_capture(&mut self) {
+        self.captures.push(Vec::new());
+    }
+
+    pub fn end_capture(&mut self) -> Vec<u8> {
+        self.captures.pop().unwrap_or_default()
+    }
 
     pub fn write_html_content(&mut self, data: &[u8]) -> IoResult<()> {
-        self.writer.write(data)?;
+        self.write(data)?;
         Ok(())
     }
 
     pub fn write_html_chunk(&mut self, data: &[U8Unit]) -> IoResult<()> {
-        for text in data {
-            self.writer.write(text.into())?;
+        // 攒成一段再写，不逐字符调用 `Writer::write`
+        let mut buf = [0u8; 512];
+        let mut len = 0;
+        for unit in data {
+            let bytes = unit.as_bytes();
+            if len + bytes.len() > buf.len() {
+                self.write(&buf[..len])?;
+                len = 0;
+            }
+            buf[len..len + bytes.len()].copy_from_slice(bytes);
+            len += bytes.len();
+        }
+        if len > 0 {
+            self.write(&buf[..len])?;
         }
         Ok(())
     }
 
-    pub fn write_html_new_line(&mut self) -> IoResult<()> {
-        self.writer.write(b"<br>")?;
-        Ok(())
+    pub fn set_abbreviations(&mut self, abbreviations: Vec<Abbreviation>) {
+        self.abbreviations = abbreviations;
     }
 
-    pub fn write_html_header_start(&mut self, level: u8) -> IoResult<()> {
-        match level {
-            1 => {
-                self.writer.write(S::h1_start().as_bytes())?;
-            }
-            2 => {
-                self.writer.write(S::h2_start().as_bytes())?;
-            }
-            3 => {
-                self.writer.write(S::h3_start().as_bytes())?;
-            }
-            4 => {
-                self.writer.write(S::h4_start().as_bytes())?;
-            }
-            5 => {
-                self.writer.write(S::h5_start().as_bytes())?;
-            }
-            6 => {
-                self.writer.write(S::h6_start().as_bytes())?;
-            }
-            _ => {
+    /// 正文文本，完整出现的缩写输出为 `<abbr>`
+    pub fn write_html_text(&mut self, data: &str) -> IoResult<()> {
+        if self.abbreviations.is_empty() || self.link_depth > 0 {
+            return self.write_html_content(data.as_bytes());
+        }
+        let abbreviations = std::mem::take(&mut self.abbreviations);
+        let res = self.write_abbreviated(data, &abbreviations);
+        self.abbreviations = abbreviations;
+        res
+    }
 
+    fn write_abbreviated(&mut self, data: &str, abbreviations: &[Abbreviation]) -> IoResult<()> {
+        let mut start = 0;
+        let mut i = 0;
+        while i < data.len() {
+            match abbr::find_at(data, i, abbreviations) {
+                Some(found) => {
+                    self.write(&data.as_bytes()[start..i])?;
+                    self.write_html_abbr_start(&found.title)?;
+                    self.write(found.abbr.as_bytes())?;
+                    self.write_html_abbr_end()?;
+                    i += found.abbr.len();
+                    start = i;
+                }
+                None => i = units::next_char(data, i),
             }
         }
+        self.write_html_content(&data.as_bytes()[start..])
+    }
+
+    pub fn write_html_abbr_start(&mut self, title: &str) -> IoResult<()> {
+        self.write(S::abbr_start(&escape_html(title)).as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_html_abbr_end(&mut self) -> IoResult<()> {
+        self.write(S::abbr_end().as_bytes())?;
+        Ok(())
+    }
+
+    /// `level` 为强调符号的个数：1 为 `<em>`，2 为 `<strong>`，3 为两者嵌套
+    pub fn write_html_emphasis_start(&mut self, level: u8) -> IoResult<()> {
+        if level & 1 == 1 {
+            self.write(S::emphasis_start().as_bytes())?;
+        }
+        if level >= 2 {
+            self.write(S::strong_start().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn write_html_emphasis_end(&mut self, level: u8) -> IoResult<()> {
+        if level >= 2 {
+            self.write(S::strong_end().as_bytes())?;
+        }
+        if level & 1 == 1 {
+            self.write(S::emphasis_end().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn write_html_code(&mut self, code: &str) -> IoResult<()> {
+        self.write(S::code_start().as_bytes())?;
+        self.write_html_escaped(code)?;
+        self.write(S::code_end().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_html_escaped(&mut self, data: &str) -> IoResult<()> {
+        // 需要转义的字符之间的内容整段写出
+        let mut start = 0;
+        for (i, b) in data.bytes().enumerate() {
+            let escaped: &[u8] = match b {
+                b'<' => b"&lt;",
+                b'>' => b"&gt;",
+                b'&' => b"&amp;",
+                b'"' => b"&quot;",
+                _ => continue,
+            };
+            self.write(&data.as_bytes()[start..i])?;
+            self.write(escaped)?;
+            start = i + 1;
+        }
+        self.write_html_content(&data.as_bytes()[start..])
+    }
+
+    pub fn write_html_new_line(&mut self) -> IoResult<()> {
+        self.write(b"<br>")?;
+        Ok(())
+    }
+
+    pub fn write_html_header_start(&mut self, level: u8, attrs: &Attributes) -> IoResult<()> {
+        if !(1..=6).contains(&level) {
+            return Ok(());
+        }
+        self.open_block();
+        let attrs = self.block_attrs(attrs);
+        if self.toc.is_some() || self.heading_ids.is_some() || self.numbers.is_some() {
+            self.open_headings.push((level, attrs.into_owned()));
+            self.begin_capture();
+        } else {
+            self.write(S::header_start(level, &attrs).as_bytes())?;
+        }
         Ok(())
     }
 
     pub fn write_html_header_end(&mut self, level: u8) -> IoResult<()> {
+        if let Some((level, mut attrs)) = self.open_headings.pop() {
+            let html = self.end_capture();
+            let text = toc::plain_text(&html);
+            let number = self.numbers.as_mut().and_then(|numbers| numbers.next(level, &mut attrs));
+            let mut permalink = None;
+            if let Some(options) = &self.heading_ids {
+                let id = match attrs.id.take() {
+                    Some(id) => {
+                        self.slugger.claim(&id);
+                        id
+                    }
+                    None => self.slugger.unique(slug::slugify(&unescape_html(&text), options.slug)),
+                };
+                permalink = options.permalink.as_ref().map(|symbol| {
+                    S::heading_anchor(&escape_html(&id), symbol).into_owned()
+                });
+                attrs.id = Some(id);
+            }
+            self.write(S::header_start(level, &attrs).as_bytes())?;
+            if let Some(number) = &number {
+                self.write(S::heading_number(number).as_bytes())?;
+            }
+            self.write(&html)?;
+            if let Some(permalink) = permalink {
+                self.write(permalink.as_bytes())?;
+            }
+            if let Some(toc) = &mut self.toc {
+                toc.entries.push(TocEntry {
+                    level,
+                    id: attrs.id,
+                    number,
+                    text,
+                });
+            }
+        }
         match level {
             1 => {
-                self.writer.write(S::h1_end().as_bytes())?;
+                self.write(S::h1_end().as_bytes())?;
             }
             2 => {
-                self.writer.write(S::h2_end().as_bytes())?;
+                self.write(S::h2_end().as_bytes())?;
             }
             3 => {
-                self.writer.write(S::h3_end().as_bytes())?;
+                self.write(S::h3_end().as_bytes())?;
             }
             4 => {
-                self.writer.write(S::h4_end().as_bytes())?;
+                self.write(S::h4_end().as_bytes())?;
             }
             5 => {
-                self.writer.write(S::h5_end().as_bytes())?;
+                self.write(S::h5_end().as_bytes())?;
             }
             6 => {
-                self.writer.write(S::h6_end().as_bytes())?;
+                self.write(S::h6_end().as_bytes())?;
             }
             _ => {
 
             }
         }
+        if (1..=6).contains(&level) {
+            self.close_block();
+        }
         Ok(())
     }
 
-    pub fn write_html_paragraph_start(&mut self) -> IoResult<()> {
-        self.writer.write(b"<p>")?;
+    pub fn write_html_paragraph_start(&mut self, attrs: &Attributes) -> IoResult<()> {
+        self.open_block();
+        let attrs = self.block_attrs(attrs);
+        self.write(S::paragraph_start(&attrs).as_bytes())?;
         Ok(())
     }
 
     pub fn write_html_paragraph_end(&mut self) -> IoResult<()> {
-        self.writer.write(b"</p>")?;
+        self.write(S::paragraph_end().as_bytes())?;
+        self.close_block();
+        Ok(())
+    }
+
+    pub fn write_html_link_start(&mut self, href: &str, title: Option<&str>, attrs: &Attributes) -> IoResult<()> {
+        self.link_depth += 1;
+        let href = escape_html(href);
+        let title = title.map(escape_html);
+        self.write(S::link_start(&href, title.as_deref(), attrs).as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_html_link_end(&mut self) -> IoResult<()> {
+        self.link_depth = self.link_depth.saturating_sub(1);
+        self.write(S::link_end().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_html_image(&mut self, src: &str, alt: &str, title: Option<&str>, attrs: &Attributes) -> IoResult<()> {
+        let title = title.map(escape_html);
+        self.write(S::image(&escape_html(src), &escape_html(alt), title.as_deref(), attrs).as_bytes())?;
         Ok(())
     }
 
     pub fn write_html_fake_header(&mut self, level: u8) -> IoResult<()> {
         for _ in 0..level {
-            self.writer.write(b"#")?;
+            self.write(b"#")?;
+        }
+        self.write(b" ")?;
+        Ok(())
+    }
+
+    pub fn write_html_math(&mut self, tex: &str, kind: MathKind, mode: MathMode) -> IoResult<()> {
+        let block = matches!(kind, MathKind::Block);
+        if block {
+            self.open_block();
+        }
+        if mode == MathMode::MathMl {
+            let mathml = math::tex_to_mathml(tex, kind.is_display());
+            self.write(mathml.as_bytes())?;
+        } else {
+            let (start, end) = match kind {
+                MathKind::Inline => (S::math_inline_start(), S::math_inline_end()),
+                MathKind::Display => (S::math_display_start(), S::math_display_end()),
+                MathKind::Block => (S::math_block_start(), S::math_block_end()),
+            };
+            self.write(start.as_bytes())?;
+            self.write_html_escaped(tex)?;
+            self.write(end.as_bytes())?;
+        }
+        if block {
+            self.close_block();
+        }
+        Ok(())
+    }
+
+    pub fn write_html_emoji(&mut self, name: &str, emoji: &str, mode: &EmojiMode) -> IoResult<()> {
+        match mode {
+            EmojiMode::Image(template) => {
+                let mut attrs = Attributes::default();
+                attrs.classes.push("emoji".to_string());
+                let src = escape_html(&emoji::image_url(template, name, emoji));
+                let title = format!(":{}:", name);
+                self.write(S::image(&src, emoji, Some(&title), &attrs).as_bytes())?;
+            }
+            _ => {
+                self.write(emoji.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_html_ruby(&mut self, ruby: &Ruby) -> IoResult<()> {
+        self.write(S::ruby_start().as_bytes())?;
+        for (base, text) in &ruby.groups {
+            self.write(S::ruby_base_start().as_bytes())?;
+            self.write_html_escaped(base)?;
+            self.write(S::ruby_base_end().as_bytes())?;
+            self.write(S::ruby_text_start().as_bytes())?;
+            self.write_html_escaped(text)?;
+            self.write(S::ruby_text_end().as_bytes())?;
+        }
+        self.write(S::ruby_end().as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_html_code_block_start(&mut self, lang: Option<&str>, attrs: &Attributes) -> IoResult<()> {
+        self.open_block();
+        let lang = lang.map(escape_html);
+        let attrs = self.block_attrs(attrs);
+        self.write(S::code_block_start(lang.as_deref(), &attrs).as_bytes())?;
+        Ok(())
+    }
+
+    pub fn write_html_code_block_end(&mut self) -> IoResult<()> {
+        self.write(S::code_block_end().as_bytes())?;
+        self.close_block();
+        Ok(())
+    }
+
+    pub fn write_html_alert_start(&mut self, kind: &str, title: Option<&str>, attrs: &Attributes) -> IoResult<()> {
+        self.open_block();
+        let kind = escape_html(&kind.to_lowercase());
+        let attrs = self.block_attrs(attrs);
+        self.write(S::alert_start(&kind, &attrs).as_bytes())?;
+        if let Some(title) = title {
+            self.write(S::alert_title_start(&kind).as_bytes())?;
+            self.write_html_escaped(title)?;
+            self.write(S::alert_title_end().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn write_html_alert_end(&mut self) -> IoResult<()> {
+        self.write(S::alert_end().as_bytes())?;
+        self.close_block();
+        Ok(())
+    }
+
+    pub fn write_html_directive(&mut self, handler: &dyn DirectiveHandler, directive: &Directive) -> IoResult<()> {
+        let block = directive.kind != DirectiveKind::Text;
+        if block {
+            self.open_block();
+        }
+        match (self.captures.last_mut(), &mut self.toc) {
+            (Some(buf), _) => handler.handle(directive, buf)?,
+            (None, Some(toc)) => handler.handle(directive, &mut toc.buffer)?,
+            // 记录输出范围时要知道处理函数写了多少字节
+            (None, None) if self.source_map.is_some() => {
+                let mut buf = Vec::new();
+                handler.handle(directive, &mut buf)?;
+                self.write(&buf)?;
+            }
+            (None, None) => handler.handle(directive, &mut self.writer)?,
+        }
+        if block {
+            self.close_block();
         }
-        self.writer.write(b" ")?;
         Ok(())
     }
 
     pub fn flush(&mut self) -> IoResult<()> {
+        if let Some(toc) = self.toc.take() {
+            let html = toc::render::<S>(&toc.entries, &toc.options);
+            // 插入的目录使之后的输出整体后移
+            if let Some(map) = &mut self.source_map {
+                let shift = |offset: usize, inclusive: bool| {
+                    let count = toc.marks.iter().filter(|&&mark| mark < offset || (inclusive && mark == offset)).count();
+                    offset + count * html.len()
+                };
+                for mapping in map.iter_mut() {
+                    mapping.output = shift(mapping.output.start, true)..shift(mapping.output.end, false);
+                }
+            }
+            let mut last = 0;
+            for mark in toc.marks {
+                self.writer.write(&toc.buffer[last..mark])?;
+                self.writer.write(html.as_bytes())?;
+                last = mark;
+            }
+            self.writer.write(&toc.buffer[last..])?;
+        }
         self.writer.flush()
     }
-}
\ No newline at end of file
+}
+
+/// `3:1-5:12` 形式的范围，列按字节计数，结束列为最后一个字节所在的列
+fn sourcepos(span: Span) -> String {
+    format!(
+        "{}:{}-{}:{}",
+        span.start.line,
+        span.start.byte_column,
+        span.end.line,
+        span.end.byte_column.saturating_sub(1).max(1)
+    )
+}
+
+/// `escape_html` 的逆操作
+pub(crate) fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
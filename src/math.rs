@@ -0,0 +1,813 @@
+//! `$...$` / `$$...$$` 数学公式的输出，以及一个实用子集的 TeX -> MathML 转换。
+//!
+//! 支持：分数、根号、上下标、希腊字母、求和/求积/积分等大型运算符、
+//! 常用关系与运算符号、`\left ... \right`、`\text`、字体命令、重音以及
+//! `matrix`/`pmatrix`/`bmatrix`/`vmatrix`/`cases`/`aligned` 环境。
+//! 不认识的命令以及嵌套过深的内容以 `<merror>` 输出，不会中断整篇文档的渲染。
+use crate::mapper::escape_html;
+
+/// 公式出现的位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MathKind {
+    /// `$x$` 或 `\(x\)`
+    Inline,
+    /// 段落中的 `$$x$$` 或 `\[x\]`
+    Display,
+    /// 独占一行（或多行）的 `$$` 块以及 ```` ```math ```` 代码块
+    Block,
+}
+
+impl MathKind {
+    pub fn is_display(self) -> bool {
+        !matches!(self, Self::Inline)
+    }
+}
+
+pub fn tex_to_mathml(tex: &str, display: bool) -> String {
+    let mut parser = TexParser {
+        chars: tex.chars().collect(),
+        pos: 0,
+        display,
+        depth: 0,
+    };
+    let mut nodes = Vec::new();
+    // 多余的 `}` 等直接跳过，保证总能产出结果
+    loop {
+        nodes.extend(parser.parse_row());
+        match parser.peek() {
+            None => break,
+            Some(_) => {
+                let stray = parser.next_raw_token();
+                nodes.push(format!("<mo>{}</mo>", escape_html(&stray)));
+            }
+        }
+    }
+    let mut out = String::from("<math xmlns=\"http://www.w3.org/1998/Math/MathML\"");
+    if display {
+        out.push_str(" display=\"block\"");
+    }
+    out.push('>');
+    out.push_str(&row(nodes));
+    out.push_str("</math>");
+    out
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Atom {
+    Ordinary,
+    // 上下标在 display 模式下放在正上/正下方
+    LargeOp,
+    // 积分号的上下标始终在右侧
+    Integral,
+    // lim、max 之类
+    Limit,
+}
+
+// 分组、命令参数和 `\left` 等的最大嵌套层数，避免 `{{{{…` 这样的输入耗尽栈
+const MAX_DEPTH: usize = 64;
+
+struct TexParser {
+    chars: Vec<char>,
+    pos: usize,
+    display: bool,
+    // 当前嵌套的层数，所有递归都经过 `parse_atom`
+    depth: usize,
+}
+
+impl TexParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(c) if c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    // 不移动位置地读取下一个命令名
+    fn peek_command(&self) -> Option<String> {
+        if self.peek() != Some('\\') {
+            return None;
+        }
+        let mut i = self.pos + 1;
+        let mut name = String::new();
+        while let Some(&c) = self.chars.get(i) {
+            if c.is_ascii_alphabetic() {
+                name.push(c);
+                i += 1;
+            } else {
+                if name.is_empty() {
+                    name.push(c);
+                }
+                break;
+            }
+        }
+        Some(name)
+    }
+
+    fn read_command(&mut self) -> String {
+        // 调用方保证当前是 '\\'
+        let name = self.peek_command().unwrap_or_default();
+        self.pos += 1 + name.chars().count();
+        name
+    }
+
+    fn next_raw_token(&mut self) -> String {
+        if self.peek() == Some('\\') {
+            format!("\\{}", self.read_command())
+        } else {
+            let c = self.peek().unwrap_or_default();
+            self.pos += 1;
+            c.to_string()
+        }
+    }
+
+    fn at_row_end(&self) -> bool {
+        match self.peek() {
+            None | Some('}') | Some('&') => true,
+            Some('\\') => matches!(
+                self.peek_command().as_deref(),
+                Some("\\") | Some("right") | Some("end")
+            ),
+            _ => false,
+        }
+    }
+
+    fn parse_row(&mut self) -> Vec<String> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.at_row_end() {
+                break;
+            }
+            if let Some((node, atom)) = self.parse_atom() {
+                let node = self.parse_scripts(node, atom);
+                nodes.push(node);
+            }
+        }
+        nodes
+    }
+
+    fn parse_group(&mut self) -> String {
+        // 当前是 '{'
+        self.pos += 1;
+        let nodes = self.parse_row();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+        }
+        row(nodes)
+    }
+
+    // 命令参数：`{...}` 或单个原子
+    fn parse_arg(&mut self) -> String {
+        self.skip_ws();
+        match self.peek() {
+            Some('{') => self.parse_group(),
+            None | Some('}') => "<mrow></mrow>".to_string(),
+            _ => self
+                .parse_atom()
+                .map(|(node, _)| node)
+                .unwrap_or_else(|| "<mrow></mrow>".to_string()),
+        }
+    }
+
+    // 原样读取 `{...}` 中的文本，用于 `\text`、环境名等
+    fn read_raw_group(&mut self) -> String {
+        self.skip_ws();
+        if self.peek() != Some('{') {
+            return self.next_raw_token();
+        }
+        self.pos += 1;
+        let mut depth = 0;
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            self.pos += 1;
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            text.push(c);
+        }
+        text
+    }
+
+    fn parse_scripts(&mut self, base: String, atom: Atom) -> String {
+        let mut sub = None;
+        let mut sup: Option<String> = None;
+        loop {
+            self.skip_ws();
+            match self.peek() {
+                Some('_') if sub.is_none() => {
+                    self.pos += 1;
+                    sub = Some(self.parse_arg());
+                }
+                Some('^') if sup.is_none() => {
+                    self.pos += 1;
+                    sup = Some(self.parse_arg());
+                }
+                Some('\'') if sup.is_none() => {
+                    let mut primes = String::new();
+                    while self.peek() == Some('\'') {
+                        self.pos += 1;
+                        primes.push('′');
+                    }
+                    sup = Some(format!("<mo>{}</mo>", primes));
+                }
+                _ => break,
+            }
+        }
+        let limits = match atom {
+            Atom::LargeOp => self.display,
+            Atom::Limit => true,
+            Atom::Ordinary | Atom::Integral => false,
+        };
+        match (sub, sup, limits) {
+            (None, None, _) => base,
+            (Some(sub), None, false) => format!("<msub>{}{}</msub>", base, sub),
+            (None, Some(sup), false) => format!("<msup>{}{}</msup>", base, sup),
+            (Some(sub), Some(sup), false) => format!("<msubsup>{}{}{}</msubsup>", base, sub, sup),
+            (Some(sub), None, true) => format!("<munder>{}{}</munder>", base, sub),
+            (None, Some(sup), true) => format!("<mover>{}{}</mover>", base, sup),
+            (Some(sub), Some(sup), true) => {
+                format!("<munderover>{}{}{}</munderover>", base, sub, sup)
+            }
+        }
+    }
+
+    fn parse_atom(&mut self) -> Option<(String, Atom)> {
+        if self.depth >= MAX_DEPTH {
+            // 剩下的内容不再解析，整体作为错误输出
+            let rest: String = self.chars[self.pos..].iter().collect();
+            self.pos = self.chars.len();
+            return Some((format!("<merror><mtext>{}</mtext></merror>", escape_html(&rest)), Atom::Ordinary));
+        }
+        self.depth += 1;
+        let atom = self.read_atom();
+        self.depth -= 1;
+        atom
+    }
+
+    fn read_atom(&mut self) -> Option<(String, Atom)> {
+        let c = self.peek()?;
+        match c {
+            '{' => Some((self.parse_group(), Atom::Ordinary)),
+            '\\' => self.parse_command(),
+            // 没有底数的上下标
+            '^' | '_' => Some(("<mrow></mrow>".to_string(), Atom::Ordinary)),
+            '0'..='9' | '.' if c != '.' || matches!(self.peek_at(1), Some('0'..='9')) => {
+                let mut num = String::new();
+                while let Some(d) = self.peek() {
+                    let is_decimal = d == '.' && matches!(self.peek_at(1), Some('0'..='9'));
+                    if d.is_ascii_digit() || is_decimal {
+                        num.push(d);
+                        self.pos += 1;
+                    } else {
+                        break;
+                    }
+                }
+                Some((format!("<mn>{}</mn>", num), Atom::Ordinary))
+            }
+            c if c.is_alphabetic() => {
+                self.pos += 1;
+                Some((format!("<mi>{}</mi>", c), Atom::Ordinary))
+            }
+            '~' => {
+                self.pos += 1;
+                Some(("<mspace width=\"0.333em\"></mspace>".to_string(), Atom::Ordinary))
+            }
+            _ => {
+                self.pos += 1;
+                let op = match c {
+                    '-' => '−',
+                    '*' => '∗',
+                    c => c,
+                };
+                Some((format!("<mo>{}</mo>", escape_html(&op.to_string())), Atom::Ordinary))
+            }
+        }
+    }
+
+    fn parse_command(&mut self) -> Option<(String, Atom)> {
+        let name = self.read_command();
+        let node = match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let num = self.parse_arg();
+                let den = self.parse_arg();
+                format!("<mfrac>{}{}</mfrac>", num, den)
+            }
+            "binom" => {
+                let n = self.parse_arg();
+                let k = self.parse_arg();
+                format!(
+                    "<mrow><mo>(</mo><mfrac linethickness=\"0\">{}{}</mfrac><mo>)</mo></mrow>",
+                    n, k
+                )
+            }
+            "sqrt" => {
+                self.skip_ws();
+                if self.peek() == Some('[') {
+                    self.pos += 1;
+                    let mut index = Vec::new();
+                    loop {
+                        self.skip_ws();
+                        match self.peek() {
+                            None => break,
+                            Some(']') => {
+                                self.pos += 1;
+                                break;
+                            }
+                            _ => {
+                                let start = self.pos;
+                                if let Some((node, _)) = self.parse_atom() {
+                                    index.push(node);
+                                }
+                                // 根指数中的 `^`、`_` 没有底数，跳过
+                                if self.pos == start {
+                                    self.pos += 1;
+                                }
+                            }
+                        }
+                    }
+                    let radicand = self.parse_arg();
+                    format!("<mroot>{}{}</mroot>", radicand, row(index))
+                } else {
+                    format!("<msqrt>{}</msqrt>", self.parse_arg())
+                }
+            }
+            "text" | "textrm" | "mbox" | "textit" | "textbf" => {
+                let text = self.read_raw_group();
+                format!("<mtext>{}</mtext>", escape_html(&text))
+            }
+            "operatorname" => {
+                let text = self.read_raw_group();
+                return Some((format!("<mi>{}</mi>", escape_html(&text)), Atom::Ordinary));
+            }
+            "mathrm" | "mathbf" | "mathit" | "mathbb" | "mathcal" | "mathfrak" | "mathsf"
+            | "mathtt" | "boldsymbol" => {
+                let variant = match name.as_str() {
+                    "mathrm" => "normal",
+                    "mathbf" | "boldsymbol" => "bold",
+                    "mathit" => "italic",
+                    "mathbb" => "double-struck",
+                    "mathcal" => "script",
+                    "mathfrak" => "fraktur",
+                    "mathsf" => "sans-serif",
+                    _ => "monospace",
+                };
+                self.skip_ws();
+                let start = self.pos;
+                let raw = self.read_raw_group();
+                if !raw.is_empty() && raw.chars().all(|c| c.is_alphanumeric()) {
+                    let tag = if raw.chars().all(|c| c.is_ascii_digit()) { "mn" } else { "mi" };
+                    format!("<{tag} mathvariant=\"{}\">{}</{tag}>", variant, raw)
+                } else {
+                    self.pos = start;
+                    format!("<mstyle mathvariant=\"{}\">{}</mstyle>", variant, self.parse_arg())
+                }
+            }
+            "hat" | "widehat" | "bar" | "overline" | "vec" | "tilde" | "widetilde" | "dot"
+            | "ddot" | "check" | "breve" | "acute" | "grave" | "overrightarrow" => {
+                let accent = match name.as_str() {
+                    "hat" | "widehat" => "^",
+                    "bar" | "overline" => "¯",
+                    "vec" | "overrightarrow" => "→",
+                    "tilde" | "widetilde" => "~",
+                    "dot" => "˙",
+                    "ddot" => "¨",
+                    "check" => "ˇ",
+                    "breve" => "˘",
+                    "acute" => "´",
+                    _ => "`",
+                };
+                let base = self.parse_arg();
+                format!("<mover accent=\"true\">{}<mo>{}</mo></mover>", base, accent)
+            }
+            "underline" => {
+                let base = self.parse_arg();
+                format!("<munder accentunder=\"true\">{}<mo>_</mo></munder>", base)
+            }
+            "left" => return Some((self.parse_left_right(), Atom::Ordinary)),
+            "begin" => {
+                let env = self.read_raw_group();
+                self.parse_environment(&env)
+            }
+            "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl" | "Bigr" | "displaystyle"
+            | "textstyle" | "limits" | "nolimits" => return self.skip_and_parse_atom(),
+            "," | ":" | ">" => "<mspace width=\"0.167em\"></mspace>".to_string(),
+            ";" => "<mspace width=\"0.278em\"></mspace>".to_string(),
+            " " => "<mspace width=\"0.333em\"></mspace>".to_string(),
+            "!" => "<mspace width=\"-0.167em\"></mspace>".to_string(),
+            "quad" => "<mspace width=\"1em\"></mspace>".to_string(),
+            "qquad" => "<mspace width=\"2em\"></mspace>".to_string(),
+            "{" | "}" | "%" | "$" | "#" | "&" | "_" => format!("<mo>{}</mo>", escape_html(&name)),
+            "|" => "<mo>‖</mo>".to_string(),
+            _ => {
+                if let Some(letter) = greek(&name) {
+                    let variant = if letter.is_uppercase() { " mathvariant=\"normal\"" } else { "" };
+                    format!("<mi{}>{}</mi>", variant, letter)
+                } else if let Some((op, atom)) = large_op(&name) {
+                    return Some((format!("<mo>{}</mo>", op), atom));
+                } else if LIMIT_FUNCTIONS.contains(&name.as_str()) {
+                    return Some((format!("<mi>{}</mi>", name), Atom::Limit));
+                } else if FUNCTIONS.contains(&name.as_str()) {
+                    format!("<mi>{}</mi>", name)
+                } else if let Some(ident) = identifier_symbol(&name) {
+                    format!("<mi>{}</mi>", ident)
+                } else if let Some(op) = operator_symbol(&name) {
+                    format!("<mo>{}</mo>", op)
+                } else {
+                    format!("<merror><mtext>\\{}</mtext></merror>", escape_html(&name))
+                }
+            }
+        };
+        Some((node, Atom::Ordinary))
+    }
+
+    fn skip_and_parse_atom(&mut self) -> Option<(String, Atom)> {
+        self.skip_ws();
+        if self.at_row_end() {
+            return None;
+        }
+        self.parse_atom()
+    }
+
+    fn read_delimiter(&mut self) -> String {
+        self.skip_ws();
+        let token = self.next_raw_token();
+        let delim = match token.as_str() {
+            "." => "",
+            "\\{" => "{",
+            "\\}" => "}",
+            "\\|" => "‖",
+            "\\langle" => "⟨",
+            "\\rangle" => "⟩",
+            "\\lfloor" => "⌊",
+            "\\rfloor" => "⌋",
+            "\\lceil" => "⌈",
+            "\\rceil" => "⌉",
+            "\\vert" => "|",
+            "\\Vert" => "‖",
+            other => return escape_html(other),
+        };
+        delim.to_string()
+    }
+
+    fn parse_left_right(&mut self) -> String {
+        let open = self.read_delimiter();
+        let mut nodes = vec![format!("<mo fence=\"true\">{}</mo>", open)];
+        loop {
+            nodes.extend(self.parse_row());
+            match self.peek_command().as_deref() {
+                Some("right") => {
+                    self.read_command();
+                    let close = self.read_delimiter();
+                    nodes.push(format!("<mo fence=\"true\">{}</mo>", close));
+                    break;
+                }
+                _ => match self.peek() {
+                    None => break,
+                    // `&`、`\\` 等不属于此处的符号按普通符号处理
+                    Some(_) => {
+                        let stray = self.next_raw_token();
+                        nodes.push(format!("<mo>{}</mo>", escape_html(&stray)));
+                    }
+                },
+            }
+        }
+        format!("<mrow>{}</mrow>", nodes.concat())
+    }
+
+    fn parse_environment(&mut self, env: &str) -> String {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut cells: Vec<String> = Vec::new();
+        loop {
+            let cell = self.parse_row();
+            cells.push(row(cell));
+            match self.peek() {
+                Some('&') => {
+                    self.pos += 1;
+                }
+                Some('\\') => match self.peek_command().as_deref() {
+                    Some("\\") => {
+                        self.read_command();
+                        rows.push(std::mem::take(&mut cells));
+                    }
+                    Some("end") => {
+                        self.read_command();
+                        self.read_raw_group();
+                        break;
+                    }
+                    // `\right` 出现在环境里，交给外层处理
+                    _ => break,
+                },
+                Some('}') => {
+                    self.pos += 1;
+                }
+                _ => break,
+            }
+        }
+        // 末尾 `\\` 产生的空行不输出
+        if !(cells.len() == 1 && cells[0] == "<mrow></mrow>") || rows.is_empty() {
+            rows.push(cells);
+        }
+
+        let align = match env {
+            "cases" => " columnalign=\"left\"",
+            "aligned" | "align" | "align*" | "split" => " columnalign=\"right left\"",
+            _ => "",
+        };
+        let mut table = format!("<mtable{}>", align);
+        for cells in rows {
+            table.push_str("<mtr>");
+            for cell in cells {
+                table.push_str("<mtd>");
+                table.push_str(&cell);
+                table.push_str("</mtd>");
+            }
+            table.push_str("</mtr>");
+        }
+        table.push_str("</mtable>");
+
+        let (open, close) = match env {
+            "pmatrix" => ("(", ")"),
+            "bmatrix" => ("[", "]"),
+            "Bmatrix" => ("{", "}"),
+            "vmatrix" => ("|", "|"),
+            "Vmatrix" => ("‖", "‖"),
+            "cases" => ("{", ""),
+            _ => return table,
+        };
+        format!(
+            "<mrow><mo fence=\"true\">{}</mo>{}<mo fence=\"true\">{}</mo></mrow>",
+            open, table, close
+        )
+    }
+}
+
+fn row(mut nodes: Vec<String>) -> String {
+    if nodes.len() == 1 {
+        nodes.pop().unwrap_or_default()
+    } else {
+        format!("<mrow>{}</mrow>", nodes.concat())
+    }
+}
+
+const LIMIT_FUNCTIONS: &[&str] = &["lim", "liminf", "limsup", "max", "min", "sup", "inf", "argmax", "argmin"];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh",
+    "tanh", "coth", "log", "ln", "lg", "exp", "det", "dim", "ker", "deg", "gcd", "hom", "arg",
+    "Pr",
+];
+
+fn greek(name: &str) -> Option<char> {
+    Some(match name {
+        "alpha" => 'α',
+        "beta" => 'β',
+        "gamma" => 'γ',
+        "delta" => 'δ',
+        "epsilon" => 'ϵ',
+        "varepsilon" => 'ε',
+        "zeta" => 'ζ',
+        "eta" => 'η',
+        "theta" => 'θ',
+        "vartheta" => 'ϑ',
+        "iota" => 'ι',
+        "kappa" => 'κ',
+        "lambda" => 'λ',
+        "mu" => 'μ',
+        "nu" => 'ν',
+        "xi" => 'ξ',
+        "omicron" => 'ο',
+        "pi" => 'π',
+        "varpi" => 'ϖ',
+        "rho" => 'ρ',
+        "varrho" => 'ϱ',
+        "sigma" => 'σ',
+        "varsigma" => 'ς',
+        "tau" => 'τ',
+        "upsilon" => 'υ',
+        "phi" => 'ϕ',
+        "varphi" => 'φ',
+        "chi" => 'χ',
+        "psi" => 'ψ',
+        "omega" => 'ω',
+        "Gamma" => 'Γ',
+        "Delta" => 'Δ',
+        "Theta" => 'Θ',
+        "Lambda" => 'Λ',
+        "Xi" => 'Ξ',
+        "Pi" => 'Π',
+        "Sigma" => 'Σ',
+        "Upsilon" => 'Υ',
+        "Phi" => 'Φ',
+        "Psi" => 'Ψ',
+        "Omega" => 'Ω',
+        _ => return None,
+    })
+}
+
+fn large_op(name: &str) -> Option<(char, Atom)> {
+    Some(match name {
+        "sum" => ('∑', Atom::LargeOp),
+        "prod" => ('∏', Atom::LargeOp),
+        "coprod" => ('∐', Atom::LargeOp),
+        "bigcup" => ('⋃', Atom::LargeOp),
+        "bigcap" => ('⋂', Atom::LargeOp),
+        "bigoplus" => ('⨁', Atom::LargeOp),
+        "bigotimes" => ('⨂', Atom::LargeOp),
+        "int" => ('∫', Atom::Integral),
+        "iint" => ('∬', Atom::Integral),
+        "iiint" => ('∭', Atom::Integral),
+        "oint" => ('∮', Atom::Integral),
+        _ => return None,
+    })
+}
+
+fn identifier_symbol(name: &str) -> Option<char> {
+    Some(match name {
+        "infty" => '∞',
+        "partial" => '∂',
+        "nabla" => '∇',
+        "emptyset" | "varnothing" => '∅',
+        "hbar" => 'ℏ',
+        "ell" => 'ℓ',
+        "Re" => 'ℜ',
+        "Im" => 'ℑ',
+        "aleph" => 'ℵ',
+        "wp" => '℘',
+        _ => return None,
+    })
+}
+
+fn operator_symbol(name: &str) -> Option<char> {
+    Some(match name {
+        "times" => '×',
+        "cdot" => '⋅',
+        "div" => '÷',
+        "pm" => '±',
+        "mp" => '∓',
+        "ast" => '∗',
+        "star" => '⋆',
+        "circ" => '∘',
+        "bullet" => '∙',
+        "oplus" => '⊕',
+        "otimes" => '⊗',
+        "leq" | "le" => '≤',
+        "geq" | "ge" => '≥',
+        "neq" | "ne" => '≠',
+        "ll" => '≪',
+        "gg" => '≫',
+        "approx" => '≈',
+        "equiv" => '≡',
+        "sim" => '∼',
+        "simeq" => '≃',
+        "cong" => '≅',
+        "propto" => '∝',
+        "to" | "rightarrow" => '→',
+        "leftarrow" | "gets" => '←',
+        "Rightarrow" | "implies" => '⇒',
+        "Leftarrow" => '⇐',
+        "leftrightarrow" => '↔',
+        "Leftrightarrow" | "iff" => '⇔',
+        "mapsto" => '↦',
+        "uparrow" => '↑',
+        "downarrow" => '↓',
+        "in" => '∈',
+        "notin" => '∉',
+        "ni" => '∋',
+        "subset" => '⊂',
+        "subseteq" => '⊆',
+        "supset" => '⊃',
+        "supseteq" => '⊇',
+        "cup" => '∪',
+        "cap" => '∩',
+        "setminus" => '∖',
+        "forall" => '∀',
+        "exists" => '∃',
+        "neg" | "lnot" => '¬',
+        "land" | "wedge" => '∧',
+        "lor" | "vee" => '∨',
+        "ldots" | "dots" => '…',
+        "cdots" => '⋯',
+        "vdots" => '⋮',
+        "ddots" => '⋱',
+        "prime" => '′',
+        "langle" => '⟨',
+        "rangle" => '⟩',
+        "lfloor" => '⌊',
+        "rfloor" => '⌋',
+        "lceil" => '⌈',
+        "rceil" => '⌉',
+        "mid" | "vert" => '|',
+        "Vert" => '‖',
+        "parallel" => '∥',
+        "perp" => '⊥',
+        "angle" => '∠',
+        "triangle" => '△',
+        "therefore" => '∴',
+        "because" => '∵',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::{MathMode, Options}, parser::{tests::render_with, ParserImpl}};
+
+    fn body(tex: &str) -> String {
+        let out = tex_to_mathml(tex, false);
+        let start = out.find('>').unwrap() + 1;
+        out[start..out.len() - "</math>".len()].to_string()
+    }
+
+    #[test]
+    fn fraction_and_scripts() {
+        assert_eq!(body("\\frac{a}{b}"), "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+        assert_eq!(body("x^2"), "<msup><mi>x</mi><mn>2</mn></msup>");
+        assert_eq!(
+            body("x_{i}^{n+1}"),
+            "<msubsup><mi>x</mi><mi>i</mi><mrow><mi>n</mi><mo>+</mo><mn>1</mn></mrow></msubsup>"
+        );
+    }
+
+    #[test]
+    fn greek_and_large_ops() {
+        assert_eq!(body("\\alpha"), "<mi>α</mi>");
+        assert_eq!(
+            tex_to_mathml("\\sum_{i=1}^n i", true),
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\"><mrow>\
+             <munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover>\
+             <mi>i</mi></mrow></math>"
+        );
+        assert_eq!(body("\\int_0^1"), "<msubsup><mo>∫</mo><mn>0</mn><mn>1</mn></msubsup>");
+    }
+
+    #[test]
+    fn matrix() {
+        assert_eq!(
+            body("\\begin{pmatrix} a & b \\\\ c & d \\end{pmatrix}"),
+            "<mrow><mo fence=\"true\">(</mo><mtable>\
+             <mtr><mtd><mi>a</mi></mtd><mtd><mi>b</mi></mtd></mtr>\
+             <mtr><mtd><mi>c</mi></mtd><mtd><mi>d</mi></mtd></mtr>\
+             </mtable><mo fence=\"true\">)</mo></mrow>"
+        );
+    }
+
+    #[test]
+    fn root_index_with_stray_scripts() {
+        assert_eq!(body("\\sqrt[3]{x}"), "<mroot><mi>x</mi><mn>3</mn></mroot>");
+        assert_eq!(body("\\sqrt[^]{x}"), "<mroot><mi>x</mi><mrow></mrow></mroot>");
+        assert_eq!(body("\\sqrt[_]{x}"), "<mroot><mi>x</mi><mrow></mrow></mroot>");
+    }
+
+    #[test]
+    fn unknown_command_and_escaping() {
+        assert_eq!(body("\\foo"), "<merror><mtext>\\foo</mtext></merror>");
+        assert_eq!(body("a<b"), "<mrow><mi>a</mi><mo>&lt;</mo><mi>b</mi></mrow>");
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        assert_eq!(body(&format!("{}x{}", "{".repeat(3), "}".repeat(3))), "<mi>x</mi>");
+        for tex in [format!("{}x<", "{".repeat(100_000)), format!("{}x<", "\\sqrt".repeat(100_000))] {
+            let out = body(&tex);
+            assert!(out.contains("x&lt;</mtext></merror>"), "{}", &out[out.len() - 80..]);
+            assert_eq!(out.matches("<merror>").count(), 1);
+        }
+    }
+
+    fn render(src: &str, math: MathMode) -> String {
+        let options = Options {
+            math,
+            ..Options::default()
+        };
+        render_with(ParserImpl::new(src.as_bytes()), &options)
+    }
+
+    #[test]
+    fn math_passthrough() {
+        assert_eq!(
+            render("a $x^2$ b\n$$\n\\frac{1}{2}\n$$\n", MathMode::Passthrough),
+            "<p>a <span class=\"math-inline\">\\(x^2\\)</span> b</p>\
+             <div class=\"math-display\">\\[\\frac{1}{2}\\]</div>"
+        );
+    }
+
+    #[test]
+    fn math_fence_to_mathml() {
+        assert_eq!(
+            render("```math\nx_1\n```\n", MathMode::MathMl),
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"block\">\
+             <msub><mi>x</mi><mn>1</mn></msub></math>"
+        );
+    }
+}
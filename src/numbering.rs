@@ -0,0 +1,182 @@
+//! 标题的层级编号，例如 `1`、`1.2`、`1.2.3`。带 `{.unnumbered}` 或 `{-}`
+//! 属性的标题不编号，也不占用编号。
+use crate::attributes::Attributes;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberingOptions {
+    /// 从这一级标题开始编号，更高级别的标题不编号
+    pub start_level: u8,
+    pub format: NumberFormat,
+}
+
+impl Default for NumberingOptions {
+    fn default() -> Self {
+        Self {
+            start_level: 2,
+            format: NumberFormat::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberFormat {
+    /// `1.2`
+    #[default]
+    Decimal,
+    /// `1.2.`
+    DecimalDot,
+    /// `A.1`，第一级用字母
+    Alpha,
+    /// `一、`、`（一）`、`1.`、`（1）`，只显示本级的编号
+    Chinese,
+}
+
+/// 按文档顺序计算编号
+#[derive(Debug)]
+pub struct HeadingNumbers {
+    options: NumberingOptions,
+    counters: [u32; 6],
+}
+
+impl HeadingNumbers {
+    pub fn new(options: NumberingOptions) -> Self {
+        Self {
+            options,
+            counters: [0; 6],
+        }
+    }
+
+    /// 返回这个标题的编号，不编号时返回 `None`；会去掉属性中的 `-`
+    pub fn next(&mut self, level: u8, attrs: &mut Attributes) -> Option<String> {
+        let excluded = attrs.classes.iter().any(|c| c == "unnumbered") || attrs.get("-").is_some();
+        attrs.pairs.retain(|(k, _)| k != "-");
+        let start = self.options.start_level.clamp(1, 6);
+        if excluded || level < start || level > 6 {
+            return None;
+        }
+        let index = (level - 1) as usize;
+        self.counters[index] += 1;
+        self.counters[index + 1..].iter_mut().for_each(|c| *c = 0);
+        Some(format(&self.counters[(start - 1) as usize..=index], self.options.format))
+    }
+}
+
+/// `path` 为从起始级别到当前级别的计数
+pub fn format(path: &[u32], format: NumberFormat) -> String {
+    let decimal = |path: &[u32]| path.iter().map(u32::to_string).collect::<Vec<_>>().join(".");
+    match format {
+        NumberFormat::Decimal => decimal(path),
+        NumberFormat::DecimalDot => format!("{}.", decimal(path)),
+        NumberFormat::Alpha => match path.split_first() {
+            Some((first, [])) => alpha(*first),
+            Some((first, rest)) => format!("{}.{}", alpha(*first), decimal(rest)),
+            None => String::new(),
+        },
+        NumberFormat::Chinese => {
+            let n = path.last().copied().unwrap_or(0);
+            match path.len() {
+                1 => format!("{}、", chinese(n)),
+                2 => format!("（{}）", chinese(n)),
+                3 => format!("{}.", n),
+                4 => format!("（{}）", n),
+                _ => decimal(&path[4..]),
+            }
+        }
+    }
+}
+
+// 1 -> A，26 -> Z，27 -> AA
+fn alpha(mut n: u32) -> String {
+    if n == 0 {
+        return "0".to_string();
+    }
+    let mut out = Vec::new();
+    while n > 0 {
+        n -= 1;
+        out.push(b'A' + (n % 26) as u8);
+        n /= 26;
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap_or_default()
+}
+
+// 小于一万的中文数字，例如 十一、二十、一百零五
+fn chinese(n: u32) -> String {
+    const DIGITS: [&str; 10] = ["零", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+    const UNITS: [&str; 4] = ["", "十", "百", "千"];
+    if n == 0 || n >= 10000 {
+        return n.to_string();
+    }
+    let digits: Vec<u32> = n.to_string().bytes().map(|b| (b - b'0') as u32).collect();
+    let mut out = String::new();
+    let mut zero = false;
+    for (i, &d) in digits.iter().enumerate() {
+        let unit = digits.len() - 1 - i;
+        if d == 0 {
+            zero = true;
+            continue;
+        }
+        if zero {
+            out.push_str(DIGITS[0]);
+            zero = false;
+        }
+        // 十一 而不是 一十一
+        if !(d == 1 && unit == 1 && out.is_empty()) {
+            out.push_str(DIGITS[d as usize]);
+        }
+        out.push_str(UNITS[unit]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, parser::{tests::render_with, ParserImpl}, toc::TocOptions};
+
+    #[test]
+    fn formats_and_exclusion() {
+        let mut numbers = HeadingNumbers::new(NumberingOptions::default());
+        let mut attrs = Attributes::default();
+        let levels = [1, 2, 3, 3, 2, 4];
+        let got: Vec<Option<String>> = levels.iter().map(|&l| numbers.next(l, &mut attrs)).collect();
+        assert_eq!(
+            got,
+            vec![None, Some("1".into()), Some("1.1".into()), Some("1.2".into()), Some("2".into()), Some("2.0.1".into())]
+        );
+        let mut skip = Attributes::default();
+        skip.pairs.push(("-".into(), String::new()));
+        assert_eq!(numbers.next(2, &mut skip), None);
+        assert!(skip.is_empty());
+        assert_eq!(numbers.next(2, &mut attrs).as_deref(), Some("3"));
+
+        assert_eq!(format(&[2, 3], NumberFormat::DecimalDot), "2.3.");
+        assert_eq!(format(&[28, 1], NumberFormat::Alpha), "AB.1");
+        assert_eq!(format(&[11], NumberFormat::Chinese), "十一、");
+        assert_eq!(format(&[1, 105], NumberFormat::Chinese), "（一百零五）");
+        assert_eq!(format(&[1, 2, 3], NumberFormat::Chinese), "3.");
+    }
+
+    #[test]
+    fn numbered_headings_share_numbers_with_toc() {
+        let options = Options {
+            toc: Some(TocOptions::default()),
+            heading_numbers: Some(NumberingOptions::default()),
+            ..Options::default()
+        };
+        let src = "# Book\n[TOC]\n## Intro\n### Scope {#scope}\n## Notes {-}\n## Usage\n";
+        let number = |n: &str| format!("<span class=\"section-number\">{n}</span> ");
+        assert_eq!(
+            render_with(ParserImpl::new(src.as_bytes()), &options),
+            format!(
+                "<h1 id=\"book\">Book</h1><br>\
+                 <nav class=\"toc\"><ul><li><a href=\"#book\">Book</a><ul>\
+                 <li><a href=\"#intro\">{}Intro</a><ul><li><a href=\"#scope\">{}Scope</a></li></ul></li>\
+                 <li><a href=\"#notes\">Notes</a></li><li><a href=\"#usage\">{}Usage</a></li></ul></li></ul></nav>\
+                 <h2 id=\"intro\">{}Intro</h2><br><h3 id=\"scope\">{}Scope</h3><br>\
+                 <h2 id=\"notes\">Notes</h2><br><h2 id=\"usage\">{}Usage</h2><br>",
+                number("1"), number("1.1"), number("2"), number("1"), number("1.1"), number("2")
+            )
+        );
+    }
+}
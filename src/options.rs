@@ -0,0 +1,119 @@
+use crate::{autolink::AutolinkOptions, cjk::CjkOptions, directive::DirectiveRegistry, include::IncludeOptions, numbering::NumberingOptions, slug::SlugAlgorithm, toc::TocOptions, variables::VariableOptions, wiki::WikiResolver};
+
+/// GitHub 支持的提示框类型
+pub const DEFAULT_ALERT_KINDS: [&str; 5] = ["NOTE", "TIP", "IMPORTANT", "WARNING", "CAUTION"];
+
+/// 引擎配置，由 `MrakdownEngine` 持有并在解析时交给 `Parser`
+#[derive(Debug)]
+pub struct Options {
+    pub math: MathMode,
+    /// `> [!NOTE]` 和 `!!! note` 可以使用的类型，不区分大小写，为空时不识别提示框
+    pub alert_kinds: Vec<String>,
+    /// 通用指令的处理函数，只有注册过的指令名才会被识别
+    pub directives: DirectiveRegistry,
+    /// 识别 `*[HTML]: Hyper Text Markup Language` 缩写定义，需要先读完整篇文档
+    pub abbreviations: bool,
+    /// 设置后识别 `[[Page]]` 维基链接
+    pub wiki_resolver: Option<Box<dyn WikiResolver>>,
+    pub emoji: EmojiMode,
+    /// 设置后识别 `[TOC]` / `[[_TOC_]]` 并缓存整篇文档的输出；目录链接到标题，
+    /// 没有设置 `heading_ids` 时按默认规则生成标题 id
+    pub toc: Option<TocOptions>,
+    /// 设置后为每个标题生成 id，`{#id}` 手写的优先
+    pub heading_ids: Option<HeadingIdOptions>,
+    /// 设置后在标题前输出层级编号，目录中使用同样的编号
+    pub heading_numbers: Option<NumberingOptions>,
+    /// 识别 `{漢字|かん|じ}` 和 `[漢字]^(kanji)` 注音
+    pub ruby: bool,
+    /// 相邻的非空行合并为一个段落，行之间输出软换行；关闭时每一行单独成段
+    pub soft_breaks: bool,
+    /// 中日韩排版规则：中日韩文字之间的软换行不输出，强调符号紧挨中日韩文字时
+    /// 放宽 CommonMark 的边界规则，可选地加入盘古之白
+    pub cjk: Option<CjkOptions>,
+    /// 设置后按其中的 URL 模板链接 `#123`、`@user` 和提交 SHA
+    pub autolink: Option<AutolinkOptions>,
+    /// 设置后展开 `{{#include path}}`，需要先读完整篇文档
+    pub include: Option<IncludeOptions>,
+    /// 设置后替换正文和链接地址中的 `{{ name }}`，并读取文档开头的 front matter
+    pub variables: Option<VariableOptions>,
+    /// 在块元素上输出 `data-sourcepos="3:1-5:12"`（列按字节计数）
+    pub source_positions: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeadingIdOptions {
+    pub slug: SlugAlgorithm,
+    /// 在标题内容后输出指向自身的链接，内容为这里的符号，例如 `¶`
+    pub permalink: Option<String>,
+}
+
+impl Options {
+    /// 是否需要在输出前扫描整篇文档
+    pub fn needs_document_pass(&self) -> bool {
+        self.abbreviations || self.include.is_some()
+    }
+
+    /// 是否自动生成标题 id；手写的 id 可能出现在后面的标题中，要先解析完整篇文档再输出
+    pub fn generates_heading_ids(&self) -> bool {
+        self.heading_ids.is_some() || self.toc.is_some()
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            math: MathMode::default(),
+            alert_kinds: DEFAULT_ALERT_KINDS.iter().map(|k| k.to_string()).collect(),
+            directives: DirectiveRegistry::default(),
+            abbreviations: false,
+            wiki_resolver: None,
+            emoji: EmojiMode::default(),
+            toc: None,
+            heading_ids: None,
+            heading_numbers: None,
+            ruby: false,
+            soft_breaks: false,
+            cjk: None,
+            autolink: None,
+            include: None,
+            variables: None,
+            source_positions: false,
+        }
+    }
+}
+
+/// `:smile:` 短代码的输出方式，未知的短代码总是原样输出
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum EmojiMode {
+    /// 不识别短代码
+    #[default]
+    Off,
+    /// 输出 Unicode emoji
+    Unicode,
+    /// 输出 `<img>`，模板中的 `{name}` 为短代码名，`{code}` 为十六进制码位，
+    /// 例如 `https://github.githubassets.com/images/icons/emoji/unicode/{code}.png`
+    Image(String),
+}
+
+impl EmojiMode {
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self, Self::Off)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MathMode {
+    /// 不识别公式，`$` 按普通文本输出
+    #[default]
+    Off,
+    /// 原样输出带 `math-inline`/`math-display` class 的元素，交给客户端 KaTeX 渲染
+    Passthrough,
+    /// 使用内置转换器输出 MathML，不依赖 JavaScript
+    MathMl,
+}
+
+impl MathMode {
+    pub fn is_enabled(self) -> bool {
+        !matches!(self, Self::Off)
+    }
+}
@@ -1,10 +1,48 @@
-use std::io::{Error, Read};
+use std::{borrow::Cow, io::{Error, Read, Result as IoResult}};
 
-use crate::{mapper::Mapper, schema::Schema, tokenizer::{Token, Tokenizer}, writer::Writer, CowStr};
+use u8unit::{Position, U8Unit};
+
+use crate::{
+    abbr::{self, Abbreviation},
+    ast::{self, Block, BlockKind, Document},
+    attributes::{self, Attributes},
+    block::{self, Container, MathOpen},
+    cjk,
+    directive,
+    document,
+    include,
+    error_handler::Diagnostic,
+    inline::{self, InlineContext, Locator}, mapper::Mapper, options::Options, schema::Schema,
+    toc,
+    visit::VisitMut,
+    tokenizer::{Span, StrToken, StrTokenizer, Token, Tokenizer}, units, variables::{self, VariableOptions}, writer::Writer, CowStr,
+};
 
 pub trait Parser {
     fn parse_and_write<S: Schema, W: Writer>(&mut self, mapper: Mapper<S, W>) -> Result<(), ParserError>;
+
+    /// 按 `options` 解析并输出。默认忽略 `options`，与 `parse_and_write` 相同
+    fn parse_and_write_with<S: Schema, W: Writer>(
+        &mut self,
+        mapper: Mapper<S, W>,
+        options: &Options,
+    ) -> Result<(), ParserError> {
+        let _ = options;
+        self.parse_and_write(mapper)
+    }
+
+    /// 取出解析过程中产生的诊断信息
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        Vec::new()
+    }
+}
+
+/// 能解析为文档树的解析器
+pub trait AstParser: Parser {
+    /// 解析为文档树而不输出，之后可以用 `Document::render` 输出
+    fn parse_to_ast(&mut self, options: &Options) -> Result<Document, ParserError>;
 }
+
 #[derive(Debug)]
 pub enum ParserError {
     SyntaxError(SyntaxError),
@@ -18,11 +56,38 @@ pub struct SyntaxError {
     column_num: u32,
 }
 
+impl SyntaxError {
+    pub fn new(msg: impl Into<CowStr>, pos: Position) -> Self {
+        Self {
+            msg: msg.into(),
+            line_num: pos.line,
+            column_num: pos.column,
+        }
+    }
+
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    pub fn line_num(&self) -> u32 {
+        self.line_num
+    }
+
+    pub fn column_num(&self) -> u32 {
+        self.column_num
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum State {
     LineStart,
     HeaderMark(u8),
+    /// 按行处理之后不再使用，保留以兼容之前的版本
     Paragraph,
+    FencedCode(Fence),
+    MathBlock(MathDelimiter),
+    // 文档开头 `---` 围起来的 front matter
+    FrontMatter,
     EOF
 }
 
@@ -36,112 +101,860 @@ impl State  {
     }
 }
 
-struct Record {
-    line_num: u32,
-    col_num: u32,
+/// ```` ``` ```` 或 `~~~` 围起来的代码块
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fence {
+    pub(crate) marker: u8,
+    pub(crate) len: usize,
+    // ```` ```math ````，内容按公式输出
+    pub(crate) math: bool,
+}
+
+/// 多行公式块的起止符号
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MathDelimiter {
+    // $$ ... $$
+    Dollar,
+    // \[ ... \]
+    Bracket,
+}
+
+impl MathDelimiter {
+    pub(crate) fn close(self) -> &'static str {
+        match self {
+            Self::Dollar => "$$",
+            Self::Bracket => "\\]",
+        }
+    }
+}
+
+// 尚未闭合的容器（提示框、指令）及其中已经完成的块
+struct OpenContainer {
+    kind: ContainerKind,
+    start: Position,
+    blocks: Vec<Block>,
+}
+
+enum ContainerKind {
+    Alert {
+        kind: String,
+        title: Option<String>,
+        attrs: Attributes,
+    },
+    Directive {
+        name: String,
+        label: Option<String>,
+        attributes: Attributes,
+    },
 }
 
 pub struct ParserImpl<R: Read> {
     tokenizer: Tokenizer<R>,
-    state: State
+    state: State,
+    // 当前行的内容，遇到换行时按块状态统一处理
+    line: String,
+    // 代码块、多行公式块已经收集到的内容
+    block: String,
+    // 代码块的语言和属性
+    code_info: (Option<String>, Attributes),
+    // 当前所在的容器（提示框、指令），由外到内，其中的行去掉前缀后按普通块处理
+    containers: Vec<Container>,
+    // 与 `containers` 一一对应，收集容器中的块
+    open: Vec<OpenContainer>,
+    // 已经完成、还没有取走的顶层块
+    blocks: Vec<Block>,
+    // admonition 中暂缓处理的空行，下一行仍属于容器时才放进容器；
+    // `pending_end` 为第一个空行之前一行的结束位置
+    pending_blank: Vec<Span>,
+    pending_end: Position,
+    // 独占一行的 `{#id .class}`，交给紧接着的下一个块
+    pending_attrs: Option<Attributes>,
+    // 开启软换行时尚未结束的段落及其属性
+    paragraph: String,
+    paragraph_attrs: Attributes,
+    // 段落从第一行行首到最后一行行尾的范围，以及其中各行的位置
+    paragraph_span: Span,
+    paragraph_locator: Locator,
+    // 已经处理到第几行，从 1 开始
+    line_num: u32,
+    // 下一个 token 的位置，当前行行首、行中第一个字符和内容结束的位置；
+    // 行首是标题标记时第一个字符在标记之后。展开 include 后按展开后的文本计算，与行号一致
+    pos: Position,
+    line_start: Position,
+    text_start: Position,
+    line_end: Position,
+    // 上一行的范围，闭合因本行不能延续的容器时使用
+    prev_line: Span,
+    // 代码块、多行公式块开始的位置
+    block_start: Position,
+    // 开启后替换 `{{ name }}`，front matter 中的变量也记在这里
+    variables: Option<VariableOptions>,
+    // 扫描整篇文档得到的缩写定义
+    abbreviations: Vec<Abbreviation>,
+    diagnostics: Vec<Diagnostic>,
+    started: bool,
+    // 需要扫描整篇文档时预先读入的全文，以及下一个 token 开始的字节
+    buffered: Option<(String, usize)>,
+    // 展开 include 后的行号到原文的映射
+    line_map: Option<include::LineMap>,
+}
+
+/// 把 token 交给 `ParserImpl` 的解析器，`Parser` 的实现由此共用
+pub(crate) trait Driver {
+    type Reader: Read;
+
+    fn parser_impl(&mut self) -> &mut ParserImpl<Self::Reader>;
+
+    /// 解析下一段输入，完成的块用 `ParserImpl::take_blocks` 取出；输入结束时返回 false
+    fn advance(&mut self, options: &Options) -> Result<bool, ParserError>;
+
+    /// 边解析边输出，每解析完一段就输出其中完成的顶层块
+    fn write_document<S: Schema, W: Writer>(&mut self, mut mapper: Mapper<S, W>, options: &Options) -> Result<(), ParserError> {
+        if self.parser_impl().is_eof() {
+            return Ok(());
+        }
+        mapper.apply_options(options);
+        let mut started = false;
+        // 生成标题 id 时先解析完整篇文档，记下手写的 id 再输出
+        let whole = options.generates_heading_ids();
+        let mut pending = Vec::new();
+        loop {
+            let more = self.advance(options)?;
+            let parser = self.parser_impl();
+            if !started {
+                // 第一段解析之前已经扫描过整篇文档
+                started = true;
+                mapper.set_abbreviations(parser.abbreviations.clone());
+            }
+            let blocks = parser.take_blocks();
+            if whole {
+                pending.extend(blocks);
+            } else {
+                ast::render_blocks(&blocks, &mut mapper, options).map_err(ParserError::IoError)?;
+            }
+            if !more {
+                break;
+            }
+        }
+        if whole {
+            mapper.claim_heading_ids(ast::heading_ids(&pending));
+            ast::render_blocks(&pending, &mut mapper, options).map_err(ParserError::IoError)?;
+        }
+        mapper.flush().map_err(ParserError::IoError)
+    }
+
+    fn build_document(&mut self, options: &Options) -> Result<Document, ParserError> {
+        if !self.parser_impl().is_eof() {
+            while self.advance(options)? {}
+        }
+        Ok(self.parser_impl().take_document())
+    }
+}
+
+/// 分词器产出的 token，`Token` 逐字符，`StrToken` 整段借用输入
+pub(crate) trait InputToken {
+    fn push_to<R: Read>(self, parser: &mut ParserImpl<R>, options: &Options);
+}
+
+impl InputToken for Token {
+    fn push_to<R: Read>(self, parser: &mut ParserImpl<R>, options: &Options) {
+        let start = parser.pos;
+        self.advance(&mut parser.pos);
+        match self {
+            Token::Header(level) => parser.push_header(level),
+            Token::Text(text) => parser.push_units(&[text]),
+            Token::TextChunk(chunk) => parser.push_units(&chunk),
+            Token::LF | Token::CRLF | Token::CR => parser.push_line_end(start, options),
+        }
+    }
+}
+
+impl InputToken for StrToken<'_> {
+    fn push_to<R: Read>(self, parser: &mut ParserImpl<R>, options: &Options) {
+        let start = parser.pos;
+        self.advance(&mut parser.pos);
+        match self {
+            StrToken::Header(level) => parser.push_header(level),
+            StrToken::Text(text) => parser.push_str(text),
+            StrToken::LF | StrToken::CRLF | StrToken::CR => parser.push_line_end(start, options),
+        }
+    }
+}
+
+impl<R: Read> Driver for ParserImpl<R> {
+    type Reader = R;
+
+    fn parser_impl(&mut self) -> &mut ParserImpl<R> {
+        self
+    }
+
+    fn advance(&mut self, options: &Options) -> Result<bool, ParserError> {
+        self.step(options)
+    }
 }
 
 impl <R: Read> Parser for ParserImpl<R> {
-    fn parse_and_write<S: Schema, W: Writer>(
-        &mut self, 
-        mut mapper: Mapper<S, W>
+    fn parse_and_write<S: Schema, W: Writer>(&mut self, mapper: Mapper<S, W>) -> Result<(), ParserError> {
+        self.parse_and_write_with(mapper, &Options::default())
+    }
+
+    fn parse_and_write_with<S: Schema, W: Writer>(
+        &mut self,
+        mapper: Mapper<S, W>,
+        options: &Options,
     ) -> Result<(), ParserError> {
+        self.write_document(mapper, options)
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+impl<R: Read> AstParser for ParserImpl<R> {
+    fn parse_to_ast(&mut self, options: &Options) -> Result<Document, ParserError> {
+        self.build_document(options)
+    }
+}
+
+impl<R: Read> ParserImpl<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            tokenizer: Tokenizer::new(reader),
+            state: State::LineStart,
+            line: String::new(),
+            block: String::new(),
+            code_info: (None, Attributes::default()),
+            containers: Vec::new(),
+            open: Vec::new(),
+            blocks: Vec::new(),
+            pending_blank: Vec::new(),
+            pending_end: Position::default(),
+            pending_attrs: None,
+            paragraph: String::new(),
+            paragraph_attrs: Attributes::default(),
+            paragraph_span: Span::default(),
+            paragraph_locator: Locator::default(),
+            line_num: 0,
+            pos: Position::default(),
+            line_start: Position::default(),
+            text_start: Position::default(),
+            line_end: Position::default(),
+            prev_line: Span::default(),
+            block_start: Position::default(),
+            variables: None,
+            abbreviations: Vec::new(),
+            diagnostics: Vec::new(),
+            started: false,
+            buffered: None,
+            line_map: None,
+        }
+    }
+
+    /// 输入从源文件中 `pos` 所在的行首开始，用于从文档中间重新解析
+    pub(crate) fn set_position(&mut self, pos: Position) {
+        self.pos = pos;
+        self.line_start = pos;
+        self.text_start = pos;
+        self.line_num = pos.line - 1;
+    }
+
+    // 开始解析前的准备，需要扫描整篇文档时在这里读完所有输入
+    fn begin(&mut self, options: &Options) -> Result<(), ParserError> {
+        self.configure(options);
+        if options.needs_document_pass() {
+            // 先读完整篇文档收集缩写等信息，再从头分词处理
+            let mut text = String::new();
+            for res in self.tokenizer.by_ref() {
+                res.map_err(ParserError::IoError)?.push_text(&mut text);
+            }
+            let expanded = match self.scan_document(&text, options) {
+                Cow::Owned(expanded) => Some(expanded),
+                Cow::Borrowed(_) => None,
+            };
+            self.buffered = Some((expanded.unwrap_or(text), 0));
+        }
+        Ok(())
+    }
+
+    /// 扫描整篇文档：应用配置，展开 include 并收集缩写定义，返回之后要解析的文本
+    pub(crate) fn scan_document<'t>(&mut self, text: &'t str, options: &Options) -> Cow<'t, str> {
+        self.configure(options);
+        let text = match &options.include {
+            Some(include) => {
+                let (expanded, line_map) = include::expand(text, include, &mut self.diagnostics);
+                self.line_map = Some(line_map);
+                Cow::Owned(expanded)
+            }
+            None => Cow::Borrowed(text),
+        };
+        self.abbreviations = document::scan(&text, options).abbreviations;
+        text
+    }
+
+    /// 处理下一个 token，第一次调用时先做开始解析前的准备；输入结束时闭合所有块并返回 false
+    pub(crate) fn step(&mut self, options: &Options) -> Result<bool, ParserError> {
         if self.state.is_eof() {
-            return Ok(());
+            return Ok(false);
         }
-        loop {
-            match self.tokenizer.next() {
-                Some(res) => match res {
-                    Ok(t) => {
-                        match self.state {
-                            State::LineStart => {
-                                match t {
-                                    Token::Header(level) => {
-                                        self.state = State::HeaderMark(level);
-                                        mapper.write_html_header_start(level).map_err(ParserError::IoError)?;
-                                    }
-
-                                    Token::Text(text) => {
-                                        self.state = State::Paragraph;
-                                        mapper.write_html_content((&text).into()).map_err(ParserError::IoError)?;
-                                    }
-                                    Token::TextChunk(chunk) => {
-                                        self.state = State::Paragraph;
-                                        mapper.write_html_chunk(&chunk).map_err(ParserError::IoError)?;
-                                    }
-
-                                    Token::LF | Token::CRLF | Token::CR => {
-                                        mapper.write_html_new_line().map_err(ParserError::IoError)?;
-                                    }
-                                }
-                            }
-                            State::HeaderMark(level) => {
-                                match t {
-                                    Token::Text(text) => {
-                                        mapper.write_html_content((&text).into()).map_err(ParserError::IoError)?;
-                                    }
-
-                                    Token::TextChunk(chunk) => {
-                                        mapper.write_html_chunk(&chunk).map_err(ParserError::IoError)?;
-                                    }
-
-                                    Token::LF | Token::CRLF | Token::CR => {
-                                        self.state = State::LineStart;
-                                        mapper.write_html_header_end(level).map_err(ParserError::IoError)?;
-                                        mapper.write_html_new_line().map_err(ParserError::IoError)?;
-                                    }
-                                    Token::Header(level) => {
-                                        mapper.write_html_fake_header(level).map_err(ParserError::IoError)?;
-                                    }
-                                }
-                            }
-                            State::Paragraph => {
-                                match t {
-                                    Token::Text(text) => {
-                                        mapper.write_html_content((&text).into()).map_err(ParserError::IoError)?;
-                                    }
-
-                                    Token::TextChunk(chunk) => {
-                                        mapper.write_html_chunk(&chunk).map_err(ParserError::IoError)?;
-                                    }
-
-                                    Token::LF | Token::CRLF | Token::CR => {
-                                        self.state = State::LineStart;
-                                        mapper.write_html_paragraph_end().map_err(ParserError::IoError)?;
-                                    }
-                                    Token::Header(level) => {
-                                        mapper.write_html_fake_header(level).map_err(ParserError::IoError)?;
-                                    }
-                                }
-                            }
-                            State::EOF => {
-                                mapper.flush().map_err(ParserError::IoError)?;
-                                return Ok(());
-                            }
+        if !self.started {
+            self.begin(options)?;
+        }
+        if let Some((text, cursor)) = self.buffered.take() {
+            // 分词器只依赖当前位置，每次从上一个 token 之后重新开始
+            let Some(token) = StrTokenizer::new(&text[cursor..]).next() else {
+                self.end_input(options);
+                return Ok(false);
+            };
+            let len = token.byte_len();
+            self.push_token(token, options);
+            self.buffered = Some((text, cursor + len));
+            return Ok(true);
+        }
+        match self.tokenizer.next() {
+            Some(res) => {
+                let t = res.map_err(ParserError::IoError)?;
+                self.push_token(t, options);
+                Ok(true)
+            }
+            None => {
+                self.end_input(options);
+                Ok(false)
+            }
+        }
+    }
+
+    pub(crate) fn push_token<T: InputToken>(&mut self, t: T, options: &Options) {
+        t.push_to(self, options)
+    }
+
+    /// 由外部分词、逐段交入 token 时的开始，只应用配置，不读取输入；
+    /// 需要扫描整篇文档的配置不能这样解析
+    pub(crate) fn begin_tokens(&mut self, options: &Options) {
+        debug_assert!(!options.needs_document_pass());
+        self.configure(options);
+    }
+
+    fn configure(&mut self, options: &Options) {
+        self.started = true;
+        self.variables = options.variables.clone();
+    }
+
+    /// 依次处理外部交入的 token，`begin_tokens` 之后调用，最后调用 `end_input`
+    pub(crate) fn push_tokens<T: InputToken>(
+        &mut self,
+        tokens: impl IntoIterator<Item = IoResult<T>>,
+        options: &Options,
+    ) -> Result<(), ParserError> {
+        for token in tokens {
+            self.push_token(token.map_err(ParserError::IoError)?, options);
+        }
+        Ok(())
+    }
+
+    /// 输入结束：闭合所有块，之后 `step` 不再读取输入
+    pub(crate) fn end_input(&mut self, options: &Options) {
+        if self.state.is_eof() {
+            return;
+        }
+        self.finish(options);
+        self.state = State::EOF;
+    }
+
+    /// 取出已经完成的顶层块，仍然打开的块留到下次；展开过 include 时行号换回顶层文档
+    pub(crate) fn take_blocks(&mut self) -> Vec<Block> {
+        let mut blocks = std::mem::take(&mut self.blocks);
+        if let Some(mut line_map) = self.line_map.as_ref() {
+            blocks.iter_mut().for_each(|block| line_map.visit_block_mut(block));
+        }
+        blocks
+    }
+
+    /// 取出已经完成的块和缩写定义
+    pub(crate) fn take_document(&mut self) -> Document {
+        Document {
+            blocks: self.take_blocks(),
+            abbreviations: self.abbreviations.clone(),
+        }
+    }
+
+    pub(crate) fn abbreviations(&self) -> &[Abbreviation] {
+        &self.abbreviations
+    }
+
+    pub(crate) fn is_eof(&self) -> bool {
+        self.state.is_eof()
+    }
+
+    fn push_header(&mut self, level: u8) {
+        if matches!(self.state, State::LineStart) && self.line.is_empty() {
+            self.state = State::HeaderMark(level);
+            self.text_start = self.pos;
+        } else {
+            // 不在行首或者在代码块中，还原成文本
+            self.push_str(&"###### "[6 - level as usize..]);
+        }
+    }
+
+    fn push_line_end(&mut self, start: Position, options: &Options) {
+        self.line_end = start;
+        self.end_line(options);
+        self.line_start = self.pos;
+        self.text_start = self.pos;
+    }
+
+    fn push_units(&mut self, text: &[U8Unit]) {
+        units::push_units(&mut self.line, text);
+    }
+
+    fn push_str(&mut self, text: &str) {
+        self.line.push_str(text);
+    }
+
+    fn end_line(&mut self, options: &Options) {
+        let mut line = std::mem::take(&mut self.line);
+        self.line_num += 1;
+        self.write_line(&line, options);
+        self.prev_line = Span::new(self.line_start, self.line_end);
+        line.clear();
+        self.line = line;
+    }
+
+    fn line_span(&self) -> Span {
+        Span::new(self.line_start, self.line_end)
+    }
+
+    // `content` 是当前行 `line` 去掉前缀后的部分，返回它在源文件中开始的位置
+    fn content_start(&self, line: &str, content: &str) -> Position {
+        let mut start = self.text_start;
+        start.advance_text(&line[..line.len() - content.len()]);
+        start
+    }
+
+    // 块完成，放进最内层的容器，不在容器中时为顶层块
+    fn push_block(&mut self, kind: BlockKind, span: Span) {
+        let block = Block { kind, span };
+        match self.open.last_mut() {
+            Some(open) => open.blocks.push(block),
+            None => self.blocks.push(block),
+        }
+    }
+
+    fn open_container(&mut self, container: Container, kind: ContainerKind) {
+        self.containers.push(container);
+        self.open.push(OpenContainer {
+            kind,
+            start: self.line_start,
+            blocks: Vec::new(),
+        });
+    }
+
+    // 解析 `text` 中的行内内容，`locator` 给出其中各处在源文件中的位置
+    fn parse_inlines(&mut self, text: &str, locator: &Locator, options: &Options) -> Vec<ast::Inline> {
+        self.inline_context(text, locator, options).parse(0..text.len())
+    }
+
+    fn inline_context<'a>(
+        &'a mut self,
+        text: &'a str,
+        locator: &'a Locator,
+        options: &'a Options,
+    ) -> InlineContext<'a> {
+        InlineContext {
+            options,
+            text,
+            locator,
+            variables: self.variables.as_ref(),
+            diagnostics: &mut self.diagnostics,
+            line_map: self.line_map.as_ref(),
+            in_link: false,
+        }
+    }
+
+    fn write_line(&mut self, line: &str, options: &Options) {
+        // 由外到内依次去掉容器前缀，第一个不能延续的容器及其内部的容器都要闭合
+        let mut content = line;
+        let mut stripped = false;
+        let mut depth = 0;
+        while depth < self.containers.len() {
+            let container = self.containers[depth];
+            if let Container::Directive(colons) = container {
+                if matches!(self.state, State::LineStart) && directive::container_close(content, colons) {
+                    return self.close_containers(depth, self.line_end, options);
+                }
+                depth += 1;
+                continue;
+            }
+            // 行首的 `#` 已经被识别为标题，说明这一行没有容器前缀
+            let inner = if self.state.is_mark_state() {
+                None
+            } else {
+                block::container_content(container, content)
+            };
+            match inner {
+                Some(inner) => {
+                    content = inner;
+                    stripped = true;
+                    depth += 1;
+                }
+                None => break,
+            }
+        }
+        if depth < self.containers.len() {
+            // 这一行不属于要闭合的容器，容器到上一个不是空行的行结束
+            let end = if self.pending_blank.is_empty() { self.prev_line.end } else { self.pending_end };
+            self.close_containers(depth, end, options);
+        } else if self.containers.last() == Some(&Container::Admonition) && units::is_blank(content) {
+            if self.pending_blank.is_empty() {
+                self.pending_end = self.prev_line.end;
+            }
+            self.pending_blank.push(self.line_span());
+            return;
+        } else {
+            self.flush_pending_blank(options);
+        }
+
+        let start = self.content_start(line, content);
+        let span = self.line_span();
+        if !matches!(self.state, State::LineStart) {
+            return self.write_block_line(content, start, span, options);
+        }
+        if options.variables.is_some() && self.line_num == 1 && variables::is_front_matter_fence(content, true) {
+            self.state = State::FrontMatter;
+            return;
+        }
+        if stripped {
+            if let Some((level, rest)) = block::header_mark(content) {
+                self.state = State::HeaderMark(level);
+                let mut start = start;
+                start.advance_text(&content[..content.len() - rest.len()]);
+                return self.write_block_line(rest, start, span, options);
+            }
+        }
+        // 缩写定义在扫描文档时已经收集，不输出；容器中的行不是定义
+        if options.abbreviations && !stripped && abbr::parse_definition(content).is_some() {
+            return self.flush_paragraph(options);
+        }
+        // 容器指令的内容在闭合时才交给处理函数，其中不能插入目录
+        let in_directive = self.containers.iter().any(|c| matches!(c, Container::Directive(_)));
+        if options.toc.is_some() && !in_directive && toc::is_marker(content) {
+            self.flush_paragraph(options);
+            self.pending_attrs = None;
+            return self.push_block(BlockKind::TocMarker, span);
+        }
+        if let Some(attrs) = block::attribute_line(content) {
+            self.flush_paragraph(options);
+            self.pending_attrs.get_or_insert_with(Attributes::default).merge(attrs);
+            return;
+        }
+        if let Some(kind) = block::alert_open(content, options) {
+            self.flush_paragraph(options);
+            let title = Some(block::default_title(&kind));
+            let attrs = self.pending_attrs.take().unwrap_or_default();
+            return self.open_container(Container::Alert, ContainerKind::Alert { kind, title, attrs });
+        }
+        if let Some((kind, title)) = block::admonition_open(content, options) {
+            self.flush_paragraph(options);
+            let title = match title {
+                Some(title) => title.to_string(),
+                None => block::default_title(&kind),
+            };
+            // `!!! note ""` 不输出标题
+            let title = Some(title).filter(|title| !title.is_empty());
+            let attrs = self.pending_attrs.take().unwrap_or_default();
+            return self.open_container(Container::Admonition, ContainerKind::Alert { kind, title, attrs });
+        }
+        if !options.directives.is_empty() {
+            if let Some((spec, colons)) = directive::container_open(content, &options.directives) {
+                self.flush_paragraph(options);
+                let mut attributes = self.pending_attrs.take().unwrap_or_default();
+                attributes.merge(spec.attributes);
+                let kind = ContainerKind::Directive {
+                    name: spec.name,
+                    label: spec.label.map(str::to_string),
+                    attributes,
+                };
+                return self.open_container(Container::Directive(colons), kind);
+            }
+            if let Some(mut spec) = directive::leaf_open(content, &options.directives) {
+                self.flush_paragraph(options);
+                if let Some(mut attributes) = self.pending_attrs.take() {
+                    attributes.merge(spec.attributes);
+                    spec.attributes = attributes;
+                }
+                // `::name[label]`，label 从行首空白和 `::name[` 之后开始
+                let children = match spec.label {
+                    Some(label) => {
+                        let offset = content.len() - units::trim_start(content).len() + 2 + spec.label_offset();
+                        let mut start = start;
+                        start.advance_text(&content[..offset]);
+                        let locator = Locator::new(start);
+                        self.parse_inlines(label, &locator, options)
+                    }
+                    None => Vec::new(),
+                };
+                let node = inline::directive_node(spec, children);
+                return self.push_block(BlockKind::LeafDirective(node), span);
+            }
+        }
+        self.write_block_line(content, start, span, options)
+    }
+
+    // 闭合 `depth` 及更内层的容器，它们在 `end` 处结束
+    fn close_containers(&mut self, depth: usize, end: Position, options: &Options) {
+        self.flush_paragraph(options);
+        while self.containers.len() > depth {
+            self.close_block(end);
+            self.containers.pop();
+            let Some(open) = self.open.pop() else {
+                continue;
+            };
+            let kind = match open.kind {
+                ContainerKind::Alert { kind, title, attrs } => BlockKind::Alert {
+                    kind,
+                    title,
+                    attrs,
+                    children: open.blocks,
+                },
+                ContainerKind::Directive { name, label, attributes } => BlockKind::Directive(ast::DirectiveNode {
+                    name,
+                    label,
+                    attributes,
+                    children: open.blocks,
+                }),
+            };
+            self.push_block(kind, Span::new(open.start, end));
+        }
+        // 此时可能已经是本行的标题状态，空行要按容器外的空行处理
+        let state = std::mem::replace(&mut self.state, State::LineStart);
+        self.flush_pending_blank(options);
+        self.state = state;
+    }
+
+    // 完成已经收集的段落
+    fn flush_paragraph(&mut self, options: &Options) {
+        if self.paragraph.is_empty() {
+            return;
+        }
+        let mut paragraph = std::mem::take(&mut self.paragraph);
+        let attrs = std::mem::take(&mut self.paragraph_attrs);
+        let locator = std::mem::take(&mut self.paragraph_locator);
+        let children = self.parse_inlines(&paragraph, &locator, options);
+        self.push_block(BlockKind::Paragraph { attrs, children }, self.paragraph_span);
+        paragraph.clear();
+        self.paragraph = paragraph;
+    }
+
+    fn flush_pending_blank(&mut self, options: &Options) {
+        for span in std::mem::take(&mut self.pending_blank) {
+            self.write_block_line("", span.start, span, options);
+        }
+    }
+
+    // 处理去掉容器前缀的一行，`start` 为其在源文件中开始的位置，`span` 为整行的范围
+    fn write_block_line(&mut self, line: &str, start: Position, span: Span, options: &Options) {
+        match self.state {
+            State::LineStart => {
+                // 开启软换行时空行只用来分隔段落
+                if options.soft_breaks && units::is_blank(line) {
+                    self.pending_attrs = None;
+                    return self.flush_paragraph(options);
+                }
+                if line.is_empty() {
+                    self.pending_attrs = None;
+                    return self.push_block(BlockKind::BlankLine, span);
+                }
+                let attrs = self.pending_attrs.take().unwrap_or_default();
+                if let Some((mut fence, lang, fence_attrs)) = block::fence_open(line) {
+                    self.flush_paragraph(options);
+                    self.block_start = span.start;
+                    self.block.clear();
+                    fence.math = options.math.is_enabled() && lang.as_deref() == Some("math");
+                    self.state = State::FencedCode(fence);
+                    let mut attrs = attrs;
+                    attrs.merge(fence_attrs);
+                    self.code_info = (lang, attrs);
+                    return;
+                }
+                if options.math.is_enabled() && self.math_block_open(line, span, options) {
+                    return;
+                }
+                if options.soft_breaks {
+                    // 相邻的行合并为一个段落，中日韩文字之间的换行不输出
+                    let trimmed = units::trim_start(line);
+                    let mut start = start;
+                    start.advance_text(&line[..line.len() - trimmed.len()]);
+                    if self.paragraph.is_empty() {
+                        self.paragraph_attrs = attrs;
+                        self.paragraph_span.start = span.start;
+                    } else {
+                        let end = units::trim_end(&self.paragraph).len();
+                        self.paragraph.truncate(end);
+                        let cjk = options.cjk.is_some();
+                        if !cjk || cjk::soft_break(self.paragraph.chars().next_back(), trimmed.chars().next()) {
+                            // 换行记在上一行的行尾
+                            self.paragraph_locator.push(self.paragraph.len(), self.paragraph_span.end);
+                            self.paragraph.push('\n');
                         }
                     }
-                    Err(_) => todo!()
+                    self.paragraph_locator.push(self.paragraph.len(), start);
+                    self.paragraph.push_str(trimmed);
+                    self.paragraph_span.end = span.end;
+                    return;
+                }
+                let children = self.parse_inlines(line, &Locator::new(start), options);
+                self.push_block(BlockKind::Paragraph { attrs, children }, span);
+            }
+            State::HeaderMark(level) => {
+                self.flush_paragraph(options);
+                self.state = State::LineStart;
+                let mut attrs = self.pending_attrs.take().unwrap_or_default();
+                let text = match attributes::trailing(line) {
+                    Some((text, trailing)) => {
+                        attrs.merge(trailing);
+                        text
+                    }
+                    None => line,
+                };
+                let children = self.parse_inlines(text, &Locator::new(start), options);
+                self.push_block(BlockKind::Heading { level, attrs, children }, span);
+            }
+            State::FencedCode(fence) => {
+                if block::fence_close(line, fence) {
+                    self.state = State::LineStart;
+                    return self.close_fence(fence, span.end);
                 }
-                None =>  {
-                    self.state = State::EOF;
-                    mapper.flush().map_err(ParserError::IoError)?;
-                    return Ok(());
+                if !fence.math && self.variables.as_ref().is_some_and(|v| v.in_code) {
+                    let locator = Locator::new(start);
+                    let line = self.inline_context(line, &locator, options).expand(line, start).into_owned();
+                    self.block.push_str(&line);
+                } else {
+                    self.block.push_str(line);
                 }
+                self.block.push('\n');
             }
+            State::MathBlock(delimiter) => {
+                if let Some(tex) = block::math_close(line, delimiter) {
+                    self.state = State::LineStart;
+                    self.block.push_str(tex);
+                    return self.close_math_block(span.end);
+                }
+                self.block.push_str(line);
+                self.block.push('\n');
+            }
+            State::FrontMatter => {
+                if variables::is_front_matter_fence(line, false) {
+                    self.state = State::LineStart;
+                } else if let Some((key, value)) = variables::parse_front_matter_line(line) {
+                    // 覆盖同名的配置
+                    if let Some(variables) = &mut self.variables {
+                        variables.values.insert(key, value);
+                    }
+                }
+            }
+            State::Paragraph | State::EOF => {}
         }
     }
-}
 
-impl<R: Read> ParserImpl<R> {
-    pub fn new(reader: R) -> Self {
-        Self { 
-            tokenizer: Tokenizer::new(reader), 
-            state: State::LineStart
+    // `$$`/`\[` 开头的行：同一行闭合则是一个公式块，否则进入公式块状态
+    fn math_block_open(&mut self, line: &str, span: Span, options: &Options) -> bool {
+        let Some((delimiter, open)) = block::math_open(line) else {
+            return false;
+        };
+        self.flush_paragraph(options);
+        match open {
+            MathOpen::Closed(tex) => self.push_block(BlockKind::Math(tex.to_string()), span),
+            MathOpen::Open(rest) => {
+                self.block_start = span.start;
+                self.block.clear();
+                if !units::is_blank(rest) {
+                    self.block.push_str(rest);
+                    self.block.push('\n');
+                }
+                self.state = State::MathBlock(delimiter);
+            }
+        }
+        true
+    }
+
+    fn close_fence(&mut self, fence: Fence, end: Position) {
+        if fence.math {
+            return self.close_math_block(end);
+        }
+        let (lang, attrs) = std::mem::take(&mut self.code_info);
+        let code = std::mem::take(&mut self.block);
+        self.push_block(BlockKind::CodeBlock { lang, attrs, code }, Span::new(self.block_start, end));
+    }
+
+    fn close_math_block(&mut self, end: Position) {
+        let tex = units::trim_end(&self.block);
+        // 公式块从开始的那一行算起
+        let tex = tex.strip_suffix('\n').unwrap_or(tex).to_string();
+        self.block.clear();
+        self.push_block(BlockKind::Math(tex), Span::new(self.block_start, end));
+    }
+
+    // 文件结尾：处理没有换行符的最后一行，并闭合仍然打开的块
+    fn finish(&mut self, options: &Options) {
+        self.line_end = self.pos;
+        if !self.line.is_empty() || self.state.is_mark_state() {
+            self.end_line(options);
+        }
+        let end = if self.pending_blank.is_empty() { self.prev_line.end } else { self.pending_end };
+        self.close_block(end);
+        self.close_containers(0, end, options);
+    }
+
+    // 闭合仍然打开的代码块或公式块
+    fn close_block(&mut self, end: Position) {
+        let state = std::mem::replace(&mut self.state, State::LineStart);
+        match state {
+            State::FencedCode(fence) => self.close_fence(fence, end),
+            State::MathBlock(_) => self.close_math_block(end),
+            _ => self.state = state,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{options::MathMode, schema::HtmlSchema};
+
+    /// 用 `parser` 输出 HTML，各种解析器的测试共用
+    pub(crate) fn render_with<P: Parser>(parser: P, options: &Options) -> String {
+        render_with_diagnostics(parser, options).0
+    }
+
+    /// 同 `render_with`，另外返回解析过程中的诊断信息
+    pub(crate) fn render_with_diagnostics<P: Parser>(mut parser: P, options: &Options) -> (String, Vec<Diagnostic>) {
+        let mut out = Vec::new();
+        parser.parse_and_write_with(Mapper::new(HtmlSchema::new(), &mut out), options).unwrap();
+        (String::from_utf8(out).unwrap(), parser.take_diagnostics())
+    }
+
+    fn render(src: &str, math: MathMode) -> String {
+        let options = Options {
+            math,
+            ..Options::default()
+        };
+        render_with(ParserImpl::new(src.as_bytes()), &options)
+    }
+
+    #[test]
+    fn parse_and_write_uses_default_options() {
+        let mut out = Vec::new();
+        ParserImpl::new("# a\n$x$".as_bytes())
+            .parse_and_write(Mapper::new(HtmlSchema::new(), &mut out))
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), render("# a\n$x$", MathMode::Off));
+    }
+
+    #[test]
+    fn fenced_code_is_escaped() {
+        assert_eq!(
+            render("```rust\n# a<b>\n```\n$x$", MathMode::Off),
+            "<pre><code class=\"language-rust\"># a&lt;b&gt;\n</code></pre><p>$x$</p>"
+        );
+    }
+}
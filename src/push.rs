@@ -0,0 +1,158 @@
+//! 推送式解析：输入分多次通过 `feed` 交进来，最后调用 `finish`，适合网络和进程间通信。
+//!
+//! 每次只把已经完整的行交给解析器，所以分块可以切在 UTF-8 字符或行的中间；块一结束就输出到
+//! `Writer`。需要整篇文档的配置（缩写、include、标题 id）会先缓存全部输入，
+//! 到 `finish` 时才输出，开启目录时输出同样要等到 `finish`。
+use std::io::{self, Result as IoResult};
+
+use crate::{
+    ast,
+    error_handler::Diagnostic,
+    mapper::Mapper,
+    options::Options,
+    parser::{Parser, ParserError, ParserImpl},
+    schema::Schema,
+    tokenizer::Tokenizer,
+    writer::Writer,
+};
+
+pub struct PushParser<'o, S: Schema, W: Writer> {
+    parser: ParserImpl<io::Empty>,
+    mapper: Mapper<S, W>,
+    options: &'o Options,
+    // 还没有凑成完整一行的字节，需要整篇文档时为全部输入
+    pending: Vec<u8>,
+    whole: bool,
+}
+
+impl<'o, S: Schema, W: Writer> PushParser<'o, S, W> {
+    pub fn new(mut mapper: Mapper<S, W>, options: &'o Options) -> Self {
+        let mut parser = ParserImpl::new(io::empty());
+        let whole = options.needs_document_pass() || options.generates_heading_ids();
+        if !whole {
+            mapper.apply_options(options);
+            parser.begin_tokens(options);
+        }
+        Self {
+            parser,
+            mapper,
+            options,
+            pending: Vec::new(),
+            whole,
+        }
+    }
+
+    /// 交入一段输入，其中完整的行立即解析
+    pub fn feed(&mut self, data: &[u8]) -> Result<(), ParserError> {
+        self.pending.extend_from_slice(data);
+        if self.whole {
+            return Ok(());
+        }
+        let Some(end) = complete_lines(&self.pending) else {
+            return Ok(());
+        };
+        let rest = self.pending.split_off(end);
+        let lines = std::mem::replace(&mut self.pending, rest);
+        self.push(&lines)
+    }
+
+    /// 输入结束：处理最后一行，闭合所有块并 flush，返回诊断信息
+    pub fn finish(mut self) -> Result<Vec<Diagnostic>, ParserError> {
+        if self.whole {
+            let mut parser = ParserImpl::new(&self.pending[..]);
+            parser.parse_and_write_with(self.mapper, self.options)?;
+            return Ok(parser.take_diagnostics());
+        }
+        let rest = std::mem::take(&mut self.pending);
+        self.push(&rest)?;
+        self.parser.end_input(self.options);
+        self.render()?;
+        self.mapper.flush().map_err(ParserError::IoError)?;
+        Ok(self.parser.take_diagnostics())
+    }
+
+    fn push(&mut self, data: &[u8]) -> Result<(), ParserError> {
+        self.parser.push_tokens(Tokenizer::new(data), self.options)?;
+        self.render()
+    }
+
+    // 输出已经完成的块
+    fn render(&mut self) -> Result<(), ParserError> {
+        let blocks = self.parser.take_blocks();
+        ast::render_blocks(&blocks, &mut self.mapper, self.options).map_err(ParserError::IoError)
+    }
+
+    /// 目前输出到的 writer，例如在两次 `feed` 之间把已经写入的内容发出去
+    pub fn writer_mut(&mut self) -> &mut W {
+        self.mapper.writer_mut()
+    }
+
+    /// flush writer，不影响尚未完成的块
+    pub fn flush(&mut self) -> IoResult<()> {
+        self.mapper.writer_mut().flush()
+    }
+}
+
+// 最后一个完整行之后的位置；末尾的 `\r` 要等下一个字节才知道是不是 `\r\n`
+fn complete_lines(data: &[u8]) -> Option<usize> {
+    let is_end = |b: &u8| *b == b'\n' || *b == b'\r';
+    let i = data.iter().rposition(is_end)?;
+    if data[i] == b'\r' && i + 1 == data.len() {
+        return data[..i].iter().rposition(is_end).map(|j| j + 1);
+    }
+    Some(i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::MathMode, parser::tests::render_with, schema::HtmlSchema};
+
+    #[test]
+    fn feed_in_any_chunks() {
+        let src = "## 标题\r\n> [!NOTE]\r> 中文 *强调*\n\n```\n代码\n```\n$$\nx^2\n$$\n末行没有换行 😊".as_bytes();
+        let options = Options {
+            math: MathMode::Passthrough,
+            soft_breaks: true,
+            ..Options::default()
+        };
+        let expected = render_with(ParserImpl::new(src), &options);
+        for size in [1, 2, 3, 5, 7, 64] {
+            let mut out = Vec::new();
+            let mut parser = PushParser::new(Mapper::new(HtmlSchema::new(), &mut out), &options);
+            for chunk in src.chunks(size) {
+                parser.feed(chunk).unwrap();
+            }
+            parser.finish().unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn output_follows_input() {
+        let mut out = Vec::new();
+        let options = Options::default();
+        let mut parser = PushParser::new(Mapper::new(HtmlSchema::new(), &mut out), &options);
+        parser.feed("# 标".as_bytes()).unwrap();
+        assert!(parser.writer_mut().is_empty());
+        parser.feed("题\n正".as_bytes()).unwrap();
+        assert_eq!(String::from_utf8_lossy(parser.writer_mut()), "<h1>标题</h1><br>");
+        parser.feed("文".as_bytes()).unwrap();
+        parser.finish().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "<h1>标题</h1><br><p>正文</p>");
+
+        // 缩写要先读完整篇文档
+        let src = "HTML 页面\n*[HTML]: Hyper Text Markup Language\n";
+        let options = Options {
+            abbreviations: true,
+            ..Options::default()
+        };
+        let mut out = Vec::new();
+        let mut parser = PushParser::new(Mapper::new(HtmlSchema::new(), &mut out), &options);
+        for chunk in src.as_bytes().chunks(4) {
+            parser.feed(chunk).unwrap();
+        }
+        parser.finish().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), render_with(ParserImpl::new(src.as_bytes()), &options));
+    }
+}
@@ -0,0 +1,82 @@
+//! 注音：`{漢字|かん|じ}` 和 `[漢字]^(kanji)`。注音的个数与基字的字符数相同时
+//! 逐字注音，只有一个时整体注音。基字按字符切分，多字节字符不会被拆开。
+use crate::units;
+
+/// 基字和注音一一对应的分组
+#[derive(Debug, PartialEq, Eq)]
+pub struct Ruby<'a> {
+    pub groups: Vec<(&'a str, &'a str)>,
+}
+
+/// `i` 处是 `{`：`{基字|注音|注音}`
+pub fn scan_braces(line: &str, i: usize) -> Option<(Ruby<'_>, usize)> {
+    let close = i + 1 + line[i + 1..].find(['}', '{'])?;
+    if line.as_bytes()[close] != b'}' {
+        return None;
+    }
+    let mut parts = line[i + 1..close].split('|');
+    let base = parts.next()?;
+    let annotations: Vec<&str> = parts.collect();
+    if annotations.iter().any(|a| units::is_blank(a)) {
+        return None;
+    }
+    Some((group(base, annotations)?, close + 1))
+}
+
+/// `i` 处是 `[`：`[基字]^(注音)`，逐字注音时注音用空白分隔
+pub fn scan_caret(line: &str, i: usize) -> Option<(Ruby<'_>, usize)> {
+    let close = units::bracket_end(line, i)?;
+    if !line[close + 1..].starts_with("^(") {
+        return None;
+    }
+    let start = close + 3;
+    let end = start + line[start..].find(')')?;
+    let annotations: Vec<&str> = line[start..end]
+        .split([' ', '\t'])
+        .filter(|a| !a.is_empty())
+        .collect();
+    let base = &line[i + 1..close];
+    let ruby = group(base, annotations.clone())
+        // 个数对不上时整体注音
+        .or_else(|| group(base, vec![units::trim(&line[start..end])]))?;
+    Some((ruby, end + 1))
+}
+
+fn group<'a>(base: &'a str, annotations: Vec<&'a str>) -> Option<Ruby<'a>> {
+    if units::is_blank(base) || annotations.is_empty() || annotations.iter().any(|a| a.is_empty()) {
+        return None;
+    }
+    let groups = if annotations.len() == 1 {
+        vec![(base, annotations[0])]
+    } else if annotations.len() == base.chars().count() {
+        let chars = base.char_indices().map(|(i, c)| &base[i..i + c.len_utf8()]);
+        chars.zip(annotations).collect()
+    } else {
+        return None;
+    };
+    Some(Ruby { groups })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braces_and_caret_forms() {
+        let line = "{漢字|かん|じ}";
+        let (ruby, end) = scan_braces(line, 0).unwrap();
+        assert_eq!(end, line.len());
+        assert_eq!(ruby.groups, vec![("漢", "かん"), ("字", "じ")]);
+
+        let line = "[東京]^(とうきょう) [漢字]^(kan ji) [x]^(a b)";
+        let (ruby, end) = scan_caret(line, 0).unwrap();
+        assert_eq!(ruby.groups, vec![("東京", "とうきょう")]);
+        let (ruby, end) = scan_caret(line, end + 1).unwrap();
+        assert_eq!(ruby.groups[1], ("字", "ji"));
+        let (ruby, _) = scan_caret(line, end + 1).unwrap();
+        assert_eq!(ruby.groups, vec![("x", "a b")]);
+
+        assert!(scan_braces("{漢字|a|b|c}", 0).is_none());
+        assert!(scan_braces("{#id .x}", 0).is_none());
+    }
+}
@@ -1,3 +1,7 @@
+use crate::{attributes::Attributes, CowStr};
+
+/// 输出各个元素时写出的标记。除 `h1_start` 到 `h6_end` 外都有默认实现，输出与 `HtmlSchema` 相同的 HTML，
+/// 只需覆盖想要改写的部分
 pub trait Schema {
     fn h1_start() -> &'static str;
     fn h1_end() -> &'static str;
@@ -11,6 +15,200 @@ pub trait Schema {
     fn h5_end() -> &'static str;
     fn h6_start() -> &'static str;
     fn h6_end() -> &'static str;
+
+    fn header_start(level: u8, attrs: &Attributes) -> CowStr {
+        if !attrs.is_empty() {
+            return format!("<h{}{}>", level, attrs.to_html()).into();
+        }
+        match level {
+            1 => Self::h1_start(),
+            2 => Self::h2_start(),
+            3 => Self::h3_start(),
+            4 => Self::h4_start(),
+            5 => Self::h5_start(),
+            _ => Self::h6_start(),
+        }
+        .into()
+    }
+
+    fn paragraph_start(attrs: &Attributes) -> CowStr {
+        if attrs.is_empty() {
+            "<p>".into()
+        } else {
+            format!("<p{}>", attrs.to_html()).into()
+        }
+    }
+
+    fn paragraph_end() -> &'static str {
+        "</p>"
+    }
+
+    fn link_start(href: &str, title: Option<&str>, attrs: &Attributes) -> CowStr {
+        match title {
+            Some(title) => format!("<a href=\"{}\" title=\"{}\"{}>", href, title, attrs.to_html()).into(),
+            None => format!("<a href=\"{}\"{}>", href, attrs.to_html()).into(),
+        }
+    }
+
+    fn link_end() -> &'static str {
+        "</a>"
+    }
+
+    fn image(src: &str, alt: &str, title: Option<&str>, attrs: &Attributes) -> CowStr {
+        match title {
+            Some(title) => {
+                format!("<img src=\"{}\" alt=\"{}\" title=\"{}\"{}>", src, alt, title, attrs.to_html()).into()
+            }
+            None => format!("<img src=\"{}\" alt=\"{}\"{}>", src, alt, attrs.to_html()).into(),
+        }
+    }
+
+    fn math_inline_start() -> &'static str {
+        "<span class=\"math-inline\">\\("
+    }
+
+    fn math_inline_end() -> &'static str {
+        "\\)</span>"
+    }
+
+    fn math_display_start() -> &'static str {
+        "<span class=\"math-display\">\\["
+    }
+
+    fn math_display_end() -> &'static str {
+        "\\]</span>"
+    }
+
+    fn math_block_start() -> &'static str {
+        "<div class=\"math-display\">\\["
+    }
+
+    fn math_block_end() -> &'static str {
+        "\\]</div>"
+    }
+
+    fn code_block_start(lang: Option<&str>, attrs: &Attributes) -> CowStr {
+        match lang {
+            Some(lang) => format!("<pre{}><code class=\"language-{}\">", attrs.to_html(), lang).into(),
+            None => format!("<pre{}><code>", attrs.to_html()).into(),
+        }
+    }
+
+    fn code_block_end() -> &'static str {
+        "</code></pre>"
+    }
+
+    fn alert_start(kind: &str, attrs: &Attributes) -> CowStr {
+        let mut attrs = attrs.clone();
+        attrs.classes.insert(0, format!("markdown-alert-{}", kind));
+        attrs.classes.insert(0, "markdown-alert".to_string());
+        format!("<div{}>", attrs.to_html()).into()
+    }
+
+    fn alert_title_start(_kind: &str) -> CowStr {
+        "<p class=\"markdown-alert-title\">".into()
+    }
+
+    fn alert_title_end() -> &'static str {
+        "</p>"
+    }
+
+    fn alert_end() -> &'static str {
+        "</div>"
+    }
+
+    fn emphasis_start() -> &'static str {
+        "<em>"
+    }
+
+    fn emphasis_end() -> &'static str {
+        "</em>"
+    }
+
+    fn strong_start() -> &'static str {
+        "<strong>"
+    }
+
+    fn strong_end() -> &'static str {
+        "</strong>"
+    }
+
+    fn code_start() -> &'static str {
+        "<code>"
+    }
+
+    fn code_end() -> &'static str {
+        "</code>"
+    }
+
+    fn abbr_start(title: &str) -> CowStr {
+        format!("<abbr title=\"{}\">", title).into()
+    }
+
+    fn abbr_end() -> &'static str {
+        "</abbr>"
+    }
+
+    fn heading_anchor(id: &str, symbol: &str) -> CowStr {
+        format!("<a class=\"anchor\" href=\"#{}\" aria-hidden=\"true\">{}</a>", id, symbol).into()
+    }
+
+    fn heading_number(number: &str) -> CowStr {
+        // 中文编号自带标点，不再加空格
+        if number.ends_with(['、', '）']) {
+            format!("<span class=\"section-number\">{}</span>", number).into()
+        } else {
+            format!("<span class=\"section-number\">{}</span> ", number).into()
+        }
+    }
+
+    fn ruby_start() -> &'static str {
+        "<ruby>"
+    }
+
+    fn ruby_end() -> &'static str {
+        "</ruby>"
+    }
+
+    fn ruby_base_start() -> &'static str {
+        "<rb>"
+    }
+
+    fn ruby_base_end() -> &'static str {
+        "</rb>"
+    }
+
+    fn ruby_text_start() -> &'static str {
+        "<rt>"
+    }
+
+    fn ruby_text_end() -> &'static str {
+        "</rt>"
+    }
+
+    fn toc_start() -> &'static str {
+        "<nav class=\"toc\">"
+    }
+
+    fn toc_end() -> &'static str {
+        "</nav>"
+    }
+
+    fn toc_list_start() -> &'static str {
+        "<ul>"
+    }
+
+    fn toc_list_end() -> &'static str {
+        "</ul>"
+    }
+
+    fn toc_item_start() -> &'static str {
+        "<li>"
+    }
+
+    fn toc_item_end() -> &'static str {
+        "</li>"
+    }
 }
 
 pub struct HtmlSchema;
@@ -63,11 +261,91 @@ impl Schema for HtmlSchema {
     fn h6_end() -> &'static str {
         "</h6>"
     }
-
 }
 
 impl HtmlSchema {
     pub fn new() -> Self {
         Self
     }
-}
\ No newline at end of file
+}
+
+impl Default for HtmlSchema {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mapper::Mapper,
+        options::Options,
+        parser::{tests::render_with, Parser, ParserImpl},
+    };
+
+    // 只实现标题，其余沿用默认的 HTML
+    struct TitleSchema;
+
+    impl Schema for TitleSchema {
+        fn h1_start() -> &'static str {
+            "<h1 class=\"title\">"
+        }
+
+        fn h1_end() -> &'static str {
+            "</h1>"
+        }
+
+        fn h2_start() -> &'static str {
+            "<h2>"
+        }
+
+        fn h2_end() -> &'static str {
+            "</h2>"
+        }
+
+        fn h3_start() -> &'static str {
+            "<h3>"
+        }
+
+        fn h3_end() -> &'static str {
+            "</h3>"
+        }
+
+        fn h4_start() -> &'static str {
+            "<h4>"
+        }
+
+        fn h4_end() -> &'static str {
+            "</h4>"
+        }
+
+        fn h5_start() -> &'static str {
+            "<h5>"
+        }
+
+        fn h5_end() -> &'static str {
+            "</h5>"
+        }
+
+        fn h6_start() -> &'static str {
+            "<h6>"
+        }
+
+        fn h6_end() -> &'static str {
+            "</h6>"
+        }
+    }
+
+    #[test]
+    fn headings_only_schema_uses_html_defaults() {
+        let src = "# 标题\n> [!NOTE]\n> *提示* `code`\n";
+        let options = Options::default();
+        let mut out = Vec::new();
+        ParserImpl::new(src.as_bytes())
+            .parse_and_write_with(Mapper::new(TitleSchema, &mut out), &options)
+            .unwrap();
+        let html = render_with(ParserImpl::new(src.as_bytes()), &options);
+        assert_eq!(String::from_utf8(out).unwrap(), html.replace("<h1>", "<h1 class=\"title\">"));
+    }
+}
@@ -0,0 +1,154 @@
+//! 标题 id：由标题的文本生成 slug，同一篇文档中重复的加 `-1`、`-2` 后缀。
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SlugAlgorithm {
+    /// 与 GitHub 一致：小写，去掉标点，每个空格换成 `-`，保留中文等非 ASCII 文字
+    #[default]
+    GitHub,
+    /// 与 Pandoc 的 `auto_identifiers` 一致：还保留 `.`，连续空白只算一个 `-`，
+    /// 去掉第一个字母之前的内容，结果为空时使用 `section`
+    Pandoc,
+    /// 只保留 ASCII 字母数字，带重音的拉丁字母转写为对应的 ASCII 字母，
+    /// 无法转写的字符（例如中文）被丢弃，结果为空时使用 `section`
+    Ascii,
+}
+
+pub fn slugify(text: &str, algorithm: SlugAlgorithm) -> String {
+    let text = text.trim();
+    match algorithm {
+        SlugAlgorithm::GitHub => text
+            .chars()
+            .filter(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '))
+            .flat_map(char::to_lowercase)
+            .map(|c| if c == ' ' { '-' } else { c })
+            .collect(),
+        SlugAlgorithm::Pandoc => {
+            let kept: String = text
+                .chars()
+                .filter(|c| c.is_alphanumeric() || c.is_whitespace() || matches!(c, '-' | '_' | '.'))
+                .flat_map(char::to_lowercase)
+                .collect();
+            let slug = kept.split_whitespace().collect::<Vec<_>>().join("-");
+            let slug = slug.trim_start_matches(|c: char| !c.is_alphabetic());
+            or_section(slug.to_string())
+        }
+        SlugAlgorithm::Ascii => {
+            let mut slug = String::new();
+            for c in text.chars().flat_map(char::to_lowercase) {
+                let folded = transliterate(c);
+                if !folded.is_empty() {
+                    slug.push_str(folded);
+                } else if c.is_ascii_alphanumeric() {
+                    slug.push(c);
+                } else if !slug.is_empty() && !slug.ends_with('-') && (c.is_whitespace() || c == '-' || c == '_') {
+                    slug.push('-');
+                }
+            }
+            or_section(slug.trim_end_matches('-').to_string())
+        }
+    }
+}
+
+fn or_section(slug: String) -> String {
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+fn transliterate(c: char) -> &'static str {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'č' => "c",
+        'ď' | 'đ' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' => "e",
+        'ğ' => "g",
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'ı' => "i",
+        'ł' | 'ľ' => "l",
+        'ñ' | 'ń' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => "o",
+        'œ' => "oe",
+        'ř' => "r",
+        'ś' | 'š' | 'ş' => "s",
+        'ß' => "ss",
+        'ť' | 'ţ' => "t",
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => "u",
+        'ý' | 'ÿ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => "",
+    }
+}
+
+/// 记录已经使用的 id
+#[derive(Debug, Default)]
+pub struct Slugger {
+    used: HashSet<String>,
+}
+
+impl Slugger {
+    /// 作者手写的 id 原样保留，只记录下来避免自动生成的 id 与之重复
+    pub fn claim(&mut self, id: &str) {
+        self.used.insert(id.to_string());
+    }
+
+    pub fn unique(&mut self, slug: String) -> String {
+        let mut id = slug.clone();
+        let mut n = 0;
+        while self.used.contains(&id) {
+            n += 1;
+            id = format!("{}-{}", slug, n);
+        }
+        self.used.insert(id.clone());
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        options::{HeadingIdOptions, Options},
+        parser::{tests::render_with, ParserImpl},
+        toc::TocOptions,
+    };
+
+    #[test]
+    fn algorithms_and_dedup() {
+        let text = "1. Café & Crème — 安装指南!";
+        assert_eq!(slugify(text, SlugAlgorithm::GitHub), "1-café--crème--安装指南");
+        assert_eq!(slugify(text, SlugAlgorithm::Pandoc), "café-crème-安装指南");
+        assert_eq!(slugify(text, SlugAlgorithm::Ascii), "1-cafe-creme");
+        assert_eq!(slugify("安装指南", SlugAlgorithm::Ascii), "section");
+
+        let mut slugger = Slugger::default();
+        slugger.claim("intro-1");
+        let ids: Vec<String> = (0..3).map(|_| slugger.unique("intro".to_string())).collect();
+        assert_eq!(ids, vec!["intro", "intro-2", "intro-3"]);
+    }
+
+    #[test]
+    fn heading_ids_are_unique() {
+        let options = Options {
+            toc: Some(TocOptions::default()),
+            heading_ids: Some(HeadingIdOptions {
+                permalink: Some("¶".to_string()),
+                ..Default::default()
+            }),
+            ..Options::default()
+        };
+        let src = "[TOC]\n## 安装指南\n## Setup & Run\n## Setup & Run\n## Custom {#setup--run-1}\n## Setup & Run\n";
+        let out = render_with(ParserImpl::new(src.as_bytes()), &options);
+        let ids: Vec<&str> = out.split("<h2 id=\"").skip(1).map(|s| &s[..s.find('"').unwrap()]).collect();
+        // 手写的 id 先于所有自动生成的 id 记录，后面出现也不会重复
+        assert_eq!(ids, vec!["安装指南", "setup--run", "setup--run-2", "setup--run-1", "setup--run-3"]);
+        let unique: HashSet<&str> = ids.iter().copied().collect();
+        assert_eq!(unique.len(), ids.len());
+        assert!(out.starts_with("<nav class=\"toc\"><ul><li><a href=\"#安装指南\">安装指南</a></li>"));
+        assert!(out.contains(
+            "<h2 id=\"setup--run\">Setup & Run<a class=\"anchor\" href=\"#setup--run\" aria-hidden=\"true\">¶</a></h2>"
+        ));
+    }
+}
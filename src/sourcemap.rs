@@ -0,0 +1,96 @@
+//! 输出到源文件的映射：记录每个块的输出字节范围和它在源文件中的 `Span`，
+//! 用于编辑器预览窗格和源码窗格之间同步滚动。
+//!
+//! 由 `Document::render_with_source_map` 生成。容器指令交给处理函数输出，其中的块不单独记录。
+use std::ops::Range;
+
+use crate::ast::Span;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceMapping {
+    /// 输出中的字节范围
+    pub output: Range<usize>,
+    pub source: Span,
+}
+
+/// 按输出位置排序，外层的块排在它包含的块之前
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    pub mappings: Vec<SourceMapping>,
+}
+
+impl SourceMap {
+    pub(crate) fn new(mut mappings: Vec<SourceMapping>) -> Self {
+        mappings.sort_by_key(|m| (m.output.start, std::cmp::Reverse(m.output.end)));
+        Self { mappings }
+    }
+
+    /// 包含输出位置 `offset` 的最内层的块
+    pub fn source_at(&self, offset: usize) -> Option<&SourceMapping> {
+        self.mappings
+            .iter()
+            .filter(|m| m.output.contains(&offset))
+            .min_by_key(|m| m.output.len())
+    }
+
+    /// 包含源文件第 `line` 行的最内层的块
+    pub fn output_at(&self, line: u32) -> Option<&SourceMapping> {
+        self.mappings
+            .iter()
+            .filter(|m| (m.source.start.line..=m.source.end.line).contains(&line))
+            .min_by_key(|m| m.source.end.offset - m.source.start.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        mapper::Mapper,
+        options::Options,
+        parser::{AstParser, Parser, ParserImpl},
+        schema::HtmlSchema,
+        toc::TocOptions,
+    };
+
+    #[test]
+    fn sourcepos_and_source_map() {
+        let src = "# 标题\n> [!NOTE]\n> 提示\n\n```\ncode\n```\n正文\n";
+        let options = Options {
+            source_positions: true,
+            ..Options::default()
+        };
+        let mut out = Vec::new();
+        ParserImpl::new(src.as_bytes())
+            .parse_and_write_with(Mapper::new(HtmlSchema::new(), &mut out), &options)
+            .unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.starts_with("<h1 data-sourcepos=\"1:1-1:8\">标题</h1><br>"));
+        assert!(html.contains("markdown-alert-note\" data-sourcepos=\"2:1-3:8\">"));
+        assert!(html.contains("<pre data-sourcepos=\"5:1-7:3\"><code>"));
+        assert!(html.ends_with("<p data-sourcepos=\"8:1-8:6\">正文</p>"));
+
+        // 目录在输出时才插入，之后的范围要随之后移
+        let options = Options {
+            toc: Some(TocOptions::default()),
+            ..Options::default()
+        };
+        let doc = ParserImpl::new(format!("[TOC]\n{}", src).as_bytes()).parse_to_ast(&options).unwrap();
+        let mut out = Vec::new();
+        let map = doc.render_with_source_map(Mapper::new(HtmlSchema::new(), &mut out), &options).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        let blocks: Vec<(&str, u32, u32)> = map
+            .mappings
+            .iter()
+            .map(|m| (&html[m.output.clone()], m.source.start.line, m.source.end.line))
+            .collect();
+        assert_eq!(blocks[0], ("<h1 id=\"标题\">标题</h1>", 2, 2));
+        assert!(blocks[1].0.starts_with("<div class=\"markdown-alert") && blocks[1].0.ends_with("</div>"));
+        assert_eq!(blocks[2], ("<p>提示</p>", 4, 4));
+        assert_eq!(blocks[3], ("<pre><code>code\n</code></pre>", 6, 8));
+        assert_eq!(blocks[4], ("<p>正文</p>", 9, 9));
+
+        let offset = html.find("提示</p>").unwrap();
+        assert_eq!(map.source_at(offset).map(|m| m.source.start.line), Some(4));
+        assert_eq!(map.output_at(7).map(|m| &html[m.output.clone()]), Some(blocks[3].0));
+    }
+}
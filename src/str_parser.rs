@@ -0,0 +1,136 @@
+//! 输入已经在内存中时的解析：分词器直接借用输入，整段文本作为一个 token，不逐字符复制和分配，
+//! 输出与 `ParserImpl` 相同。
+//!
+//! 需要整篇文档的配置（缩写、include）直接扫描借用的输入，没有 include 时同样不复制。
+use std::{io, str::Utf8Error};
+
+use crate::{
+    ast::Document,
+    error_handler::Diagnostic,
+    mapper::Mapper,
+    options::Options,
+    parser::{AstParser, Driver, Parser, ParserError, ParserImpl},
+    schema::Schema,
+    tokenizer::StrTokenizer,
+    writer::Writer,
+};
+
+pub struct StrParser<'a> {
+    tokens: StrTokenizer<'a>,
+    input: &'a str,
+    // 只用它的行处理，token 由 `tokens` 交入
+    parser: ParserImpl<io::Empty>,
+}
+
+impl<'a> StrParser<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            tokens: StrTokenizer::new(input),
+            input,
+            parser: ParserImpl::new(io::empty()),
+        }
+    }
+
+    pub fn from_bytes(input: &'a [u8]) -> Result<Self, Utf8Error> {
+        std::str::from_utf8(input).map(Self::new)
+    }
+}
+
+impl Driver for StrParser<'_> {
+    type Reader = io::Empty;
+
+    fn parser_impl(&mut self) -> &mut ParserImpl<io::Empty> {
+        &mut self.parser
+    }
+
+    fn advance(&mut self, options: &Options) -> Result<bool, ParserError> {
+        // 一次交入全部 token
+        if options.needs_document_pass() {
+            let text = self.parser.scan_document(self.input, options);
+            self.parser.push_tokens(StrTokenizer::new(&text).map(Ok), options)?;
+        } else {
+            self.parser.begin_tokens(options);
+            self.parser.push_tokens(self.tokens.by_ref().map(Ok), options)?;
+        }
+        self.parser.end_input(options);
+        Ok(false)
+    }
+}
+
+impl Parser for StrParser<'_> {
+    fn parse_and_write<S: Schema, W: Writer>(&mut self, mapper: Mapper<S, W>) -> Result<(), ParserError> {
+        self.parse_and_write_with(mapper, &Options::default())
+    }
+
+    fn parse_and_write_with<S: Schema, W: Writer>(
+        &mut self,
+        mapper: Mapper<S, W>,
+        options: &Options,
+    ) -> Result<(), ParserError> {
+        self.write_document(mapper, options)
+    }
+
+    fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        self.parser.take_diagnostics()
+    }
+}
+
+impl AstParser for StrParser<'_> {
+    fn parse_to_ast(&mut self, options: &Options) -> Result<Document, ParserError> {
+        self.build_document(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::MathMode, parser::tests::render_with, tokenizer::{StrToken, Tokenizer}};
+
+    #[test]
+    fn same_output_as_reader() {
+        let src = "## 标题 {#top}\r\n正文 # 不是标题 ####### 七个 ######## 八个\r> [!NOTE]\n> 中文 *强调* 😊\n\n\
+                   ```\n# 代码\n```\n$$\nx^2\n$$\n*[HTML]: Hyper Text\nHTML ###### \n末行没有换行 #";
+        for options in [
+            Options {
+                math: MathMode::Passthrough,
+                soft_breaks: true,
+                ..Options::default()
+            },
+            Options {
+                abbreviations: true,
+                ..Options::default()
+            },
+            Options {
+                source_positions: true,
+                ..Options::default()
+            },
+        ] {
+            // 长输入的文本段会跨过读取缓冲区
+            for src in [src.to_string(), src.repeat(100)] {
+                assert_eq!(render_with(StrParser::new(&src), &options), render_with(ParserImpl::new(src.as_bytes()), &options));
+            }
+        }
+
+        let options = Options::default();
+        let tree = StrParser::new(src).parse_to_ast(&options).unwrap();
+        assert_eq!(tree, ParserImpl::new(src.as_bytes()).parse_to_ast(&options).unwrap());
+        assert!(StrParser::from_bytes(b"\xff").is_err());
+
+        // token 首尾相接，文本成段借用输入，结束位置与逐字符分词相同
+        let mut tokens = StrTokenizer::new(src);
+        let mut end = Default::default();
+        let mut texts = 0;
+        while let Some((token, span)) = tokens.next_spanned() {
+            assert_eq!(span.start, end);
+            end = span.end;
+            texts += matches!(token, StrToken::Text(_)) as usize;
+        }
+        let mut reader = Tokenizer::new(src.as_bytes());
+        let mut last = Default::default();
+        while let Some(res) = reader.next_spanned() {
+            last = res.unwrap().1.end;
+        }
+        assert_eq!(end, last);
+        assert!(texts < src.chars().count() / 4);
+    }
+}
@@ -0,0 +1,149 @@
+//! `[TOC]` / `[[_TOC_]]` 目录。开启后 `Mapper` 缓存整篇文档的输出，
+//! 记录经过它输出的所有标题，最后在标记的位置插入目录。
+use crate::{attributes::Attributes, schema::Schema, units};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocOptions {
+    /// 收入目录的最小和最大标题级别
+    pub min_level: u8,
+    pub max_level: u8,
+    /// 没有标记时也在文档开头输出目录
+    pub prepend: bool,
+}
+
+impl Default for TocOptions {
+    fn default() -> Self {
+        Self {
+            min_level: 1,
+            max_level: 6,
+            prepend: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TocEntry {
+    pub level: u8,
+    pub id: Option<String>,
+    /// 标题的编号，与正文中的一致
+    pub number: Option<String>,
+    /// 标题渲染后去掉标签的内容，其中的实体保持转义
+    pub text: String,
+}
+
+pub fn is_marker(line: &str) -> bool {
+    matches!(units::trim(line), "[TOC]" | "[[_TOC_]]")
+}
+
+/// 去掉 HTML 标签，避免标题中的链接嵌套在目录的链接中
+pub fn plain_text(html: &[u8]) -> String {
+    let html = String::from_utf8_lossy(html);
+    let mut out = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// 按级别输出嵌套的列表，级别跳跃时只嵌套一层
+pub fn render<S: Schema>(entries: &[TocEntry], options: &TocOptions) -> String {
+    let mut out = String::from(S::toc_start());
+    let mut levels: Vec<u8> = Vec::new();
+    let entries = entries
+        .iter()
+        .filter(|e| (options.min_level..=options.max_level).contains(&e.level));
+    for entry in entries {
+        while levels.last().is_some_and(|&top| top > entry.level) {
+            levels.pop();
+            out.push_str(S::toc_item_end());
+            out.push_str(S::toc_list_end());
+        }
+        if levels.last() == Some(&entry.level) {
+            out.push_str(S::toc_item_end());
+        } else {
+            out.push_str(S::toc_list_start());
+            levels.push(entry.level);
+        }
+        out.push_str(S::toc_item_start());
+        let href = entry.id.as_ref().map(|id| format!("#{}", crate::mapper::escape_html(id)));
+        if let Some(href) = &href {
+            out.push_str(&S::link_start(href, None, &Attributes::default()));
+        }
+        if let Some(number) = &entry.number {
+            out.push_str(&S::heading_number(number));
+        }
+        out.push_str(&entry.text);
+        if href.is_some() {
+            out.push_str(S::link_end());
+        }
+    }
+    for _ in levels {
+        out.push_str(S::toc_item_end());
+        out.push_str(S::toc_list_end());
+    }
+    out.push_str(S::toc_end());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, parser::{tests::render_with, ParserImpl}};
+    use crate::schema::HtmlSchema;
+
+    #[test]
+    fn nested_lists_follow_levels() {
+        let entry = |level, id: Option<&str>, text: &str| TocEntry {
+            level,
+            id: id.map(String::from),
+            number: None,
+            text: text.to_string(),
+        };
+        let entries = [
+            entry(1, Some("a"), "A"),
+            entry(2, Some("b"), "B"),
+            entry(4, None, "D"),
+            entry(2, Some("c"), "C"),
+            entry(1, Some("e"), "E"),
+        ];
+        let options = TocOptions {
+            max_level: 4,
+            ..TocOptions::default()
+        };
+        assert_eq!(
+            render::<HtmlSchema>(&entries, &options),
+            "<nav class=\"toc\"><ul><li><a href=\"#a\">A</a><ul><li><a href=\"#b\">B</a>\
+             <ul><li>D</li></ul></li><li><a href=\"#c\">C</a></li></ul></li>\
+             <li><a href=\"#e\">E</a></li></ul></nav>"
+        );
+        assert!(is_marker(" [TOC] "));
+        assert!(!is_marker("[TOC] x"));
+        assert_eq!(plain_text(b"a <code>b</code> &amp; <a href=\"x\">c</a>"), "a b &amp; c");
+    }
+
+    #[test]
+    fn toc_is_inserted_at_markers() {
+        let options = Options {
+            toc: Some(TocOptions {
+                min_level: 2,
+                ..Default::default()
+            }),
+            ..Options::default()
+        };
+        let src = "# Title\n[TOC]\n## One {#one}\n### [Sub](/s) {#sub}\n## Two\n```\n[TOC]\n```\n";
+        assert_eq!(
+            render_with(ParserImpl::new(src.as_bytes()), &options),
+            "<h1 id=\"title\">Title</h1><br>\
+             <nav class=\"toc\"><ul><li><a href=\"#one\">One</a><ul><li><a href=\"#sub\">Sub</a></li></ul></li>\
+             <li><a href=\"#two\">Two</a></li></ul></nav>\
+             <h2 id=\"one\">One</h2><br><h3 id=\"sub\"><a href=\"/s\">Sub</a></h3><br><h2 id=\"two\">Two</h2><br>\
+             <pre><code>[TOC]\n</code></pre>"
+        );
+    }
+}
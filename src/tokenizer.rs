@@ -1,6 +1,11 @@
-use std::{io::Read, iter::Peekable};
+use std::io::Read;
 use std::io::Result as IoResult;
-use u8unit::{CharIterator, U8Unit};
+use u8unit::{CharIterator, Position, U8Unit};
+
+use crate::units;
+
+// 会打断一段文本的字节
+const SPECIAL: &[u8] = b"\r\n#";
 
 pub enum Token {
     //just 1 ~ 6
@@ -13,6 +18,54 @@ pub enum Token {
 }
 
 
+impl Token {
+    /// 从 `pos` 越过这个 token 在源文件中的字节
+    pub fn advance(&self, pos: &mut Position) {
+        let hash = U8Unit::from(b'#');
+        match self {
+            Self::Header(level) => {
+                for _ in 0..*level {
+                    pos.advance(&hash, false);
+                }
+                pos.advance(&U8Unit::from(b' '), false);
+            }
+            Self::CR => pos.advance(&U8Unit::from(b'\r'), false),
+            Self::LF => pos.advance(&U8Unit::from(b'\n'), false),
+            Self::CRLF => {
+                pos.advance(&U8Unit::from(b'\r'), false);
+                pos.advance(&U8Unit::from(b'\n'), true);
+            }
+            Self::Text(unit) => pos.advance(unit, false),
+            Self::TextChunk(chunk) => chunk.iter().for_each(|unit| pos.advance(unit, false)),
+        }
+    }
+
+    /// 把这个 token 在源文件中的文本接到 `out` 后面
+    pub fn push_text(&self, out: &mut String) {
+        match self {
+            Self::Header(level) => out.push_str(&"###### "[6 - *level as usize..]),
+            Self::CR => out.push('\r'),
+            Self::LF => out.push('\n'),
+            Self::CRLF => out.push_str("\r\n"),
+            Self::Text(unit) => units::push_units(out, std::slice::from_ref(unit)),
+            Self::TextChunk(chunk) => units::push_units(out, chunk),
+        }
+    }
+}
+
+/// 在源文件中的范围，`end` 是最后一个字符之后的位置
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+}
+
 impl std::fmt::Debug for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -27,46 +80,82 @@ impl std::fmt::Debug for Token {
 }
 
 pub struct Tokenizer<R: Read> {
-    iter: Peekable<CharIterator<R>>,
+    iter: CharIterator<R>,
+    // 向后看的一个字符，`Some(None)` 表示输入已经结束
+    peeked: Option<Option<IoResult<U8Unit>>>,
     //用于存储‘#’等可能的标识符方便解析
-    cache: Vec<U8Unit>
+    cache: Vec<U8Unit>,
+    // 下一个 token 的起始位置
+    pos: Position,
 }
 
 impl <R: Read> Tokenizer<R> {
     
     pub fn new(r: R) -> Self {
         Self {
-            iter: CharIterator::new(r).peekable(),
-            cache: Vec::new()
+            iter: CharIterator::new(r),
+            peeked: None,
+            cache: Vec::new(),
+            pos: Position::default(),
         }
     }
-}
 
-impl<R: Read> Iterator for Tokenizer<R> {
-    type Item = IoResult<Token>;
+    /// 下一个 token 及其在源文件中的范围
+    pub fn next_spanned(&mut self) -> Option<IoResult<(Token, Span)>> {
+        let start = self.pos;
+        Some(self.next_token()?.map(|token| {
+            token.advance(&mut self.pos);
+            (token, Span::new(start, self.pos))
+        }))
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_unit(&mut self) -> Option<IoResult<U8Unit>> {
+        match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.iter.next(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<&IoResult<U8Unit>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.iter.next());
+        }
+        self.peeked.as_ref().and_then(Option::as_ref)
+    }
+
+    // 以 `first` 开头的一段文本，缓冲区中之后的普通字符一次读出
+    fn text_run(&mut self, first: U8Unit) -> Token {
+        if self.peeked.is_some() {
+            return Token::Text(first);
+        }
+        let mut run = Vec::new();
+        run.push(first);
+        if self.iter.next_run(&mut run, SPECIAL) == 0 {
+            return Token::Text(first);
+        }
+        Token::TextChunk(run)
+    }
+
+    fn next_token(&mut self) -> Option<IoResult<Token>> {
         
         'outer: loop {
 
             if !self.cache.is_empty() {
-                let res = Some(Ok(Token::TextChunk(self.cache.clone())));
-                self.cache.clear();
-                return res;
+                return Some(Ok(Token::TextChunk(std::mem::take(&mut self.cache))));
             }
 
-            let unit = self.iter.next()?;
+            let unit = self.next_unit()?;
             match unit {
                 Ok(chr) => {
                     return Some(Ok(match chr {
                         U8Unit::OneByte(byte) => {
                             match byte[0] {
                                 b'\n' => Token::LF,
-                                b'\r' => match self.iter.peek() {
+                                b'\r' => match self.peek() {
                                     Some(e) => match e {
                                         Ok(u) => {
                                             if u == b'\n' {
-                                                self.iter.next();
+                                                self.next_unit();
                                                 Token::CRLF
                                             } else {
                                                 Token::CR
@@ -78,42 +167,43 @@ impl<R: Read> Iterator for Tokenizer<R> {
                                 }
                                 b'#' => {
                                     let mut head_level = 1;
+                                    let mut header = false;
                                     self.cache.push(chr);
-                                    while let Some(res) = self.iter.peek() {
+                                    while let Some(res) = self.peek() {
                                         match res {
                                             Ok(res) => {
                                                 if res == b'#' {
                                                     head_level += 1;
-                                                    self.cache.push(*res);
-                                                    self.iter.next();
+                                                    self.cache.push(U8Unit::from(b'#'));
+                                                    self.next_unit();
                                                     // "#" more than 7
                                                     if head_level > 6 {
                                                         continue 'outer;
                                                     }
                                                 } else if res == b' ' {
                                                     // #### 解析为header标识
-                                                    self.iter.next();
+                                                    self.next_unit();
                                                     self.cache.clear();
+                                                    header = true;
                                                     break;
                                                 } else {
                                                     continue 'outer;
                                                 }
                                             }
-                                            Err(_) => {
-
-                                            }
+                                            // 先把缓存的 '#' 作为文本返回，错误在下一轮交出去
+                                            Err(_) => continue 'outer,
                                         }
                                     }
+                                    // 文件在 '#' 之后结束，不是标题
+                                    if !header {
+                                        continue 'outer;
+                                    }
                                     Token::Header(head_level)
                                 }
-                                _ => {
-                                    Token::Text(chr)
-                                }
+                                _ => self.text_run(chr),
                             }
                         }
-                        _ => {
-                            Token::Text(chr)
-                        }
+                        _ => self.text_run(chr),
                     }))
                 }
                 Err(e) => {
@@ -124,6 +214,132 @@ impl<R: Read> Iterator for Tokenizer<R> {
     }
 }
 
+impl<R: Read> Iterator for Tokenizer<R> {
+    type Item = IoResult<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+
+/// 借用内存中输入的 token，`Text` 是一整段不含换行、不构成标题标记的文本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrToken<'a> {
+    //just 1 ~ 6
+    Header(u8),
+    CR,
+    LF,
+    CRLF,
+    Text(&'a str),
+}
+
+impl StrToken<'_> {
+    /// 从 `pos` 越过这个 token 在源文件中的字节
+    pub fn advance(&self, pos: &mut Position) {
+        match self {
+            Self::Header(level) => pos.advance_text(&"###### "[6 - *level as usize..]),
+            Self::CR => pos.advance(&U8Unit::from(b'\r'), false),
+            Self::LF => pos.advance(&U8Unit::from(b'\n'), false),
+            Self::CRLF => {
+                pos.advance(&U8Unit::from(b'\r'), false);
+                pos.advance(&U8Unit::from(b'\n'), true);
+            }
+            Self::Text(text) => pos.advance_text(text),
+        }
+    }
+    /// 在源文件中的字节数
+    pub fn byte_len(&self) -> usize {
+        match self {
+            Self::Header(level) => *level as usize + 1,
+            Self::CR | Self::LF => 1,
+            Self::CRLF => 2,
+            Self::Text(text) => text.len(),
+        }
+    }
+}
+
+/// 内存中输入的分词器，切分规则与 `Tokenizer` 相同，文本不逐字符复制
+pub struct StrTokenizer<'a> {
+    input: &'a str,
+    // 下一个 token 的起始字节
+    cursor: usize,
+    pos: Position,
+}
+
+impl<'a> StrTokenizer<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            cursor: 0,
+            pos: Position::default(),
+        }
+    }
+
+    pub fn from_bytes(input: &'a [u8]) -> Result<Self, std::str::Utf8Error> {
+        std::str::from_utf8(input).map(Self::new)
+    }
+
+    /// 下一个 token 及其在源文件中的范围
+    pub fn next_spanned(&mut self) -> Option<(StrToken<'a>, Span)> {
+        let start = self.pos;
+        let token = self.next()?;
+        token.advance(&mut self.pos);
+        Some((token, Span::new(start, self.pos)))
+    }
+
+    fn next_token(&mut self) -> Option<StrToken<'a>> {
+        let bytes = &self.input.as_bytes()[self.cursor..];
+        let (token, len) = match bytes {
+            [] => return None,
+            [b'\r', b'\n', ..] => (StrToken::CRLF, 2),
+            [b'\r', ..] => (StrToken::CR, 1),
+            [b'\n', ..] => (StrToken::LF, 1),
+            _ => match header(bytes) {
+                Some(level) => (StrToken::Header(level), level as usize + 1),
+                None => {
+                    let len = text_run(bytes);
+                    (StrToken::Text(&self.input[self.cursor..self.cursor + len]), len)
+                }
+            },
+        };
+        self.cursor += len;
+        Some(token)
+    }
+}
+
+impl<'a> Iterator for StrTokenizer<'a> {
+    type Item = StrToken<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+// `bytes` 开头的 1 ~ 6 个 '#' 加一个空格是标题标记
+fn header(bytes: &[u8]) -> Option<u8> {
+    let level = bytes.iter().take(7).take_while(|b| **b == b'#').count();
+    (1..=6).contains(&level).then_some(())?;
+    (bytes.get(level) == Some(&b' ')).then_some(level as u8)
+}
+
+// 开头一段文本的长度，到换行或标题标记为止；
+// 与 `Tokenizer` 一样，连续 7 个 '#' 作为文本，之后重新计数
+fn text_run(bytes: &[u8]) -> usize {
+    let mut i = 0;
+    while let Some(found) = u8unit::find_any(&bytes[i..], SPECIAL) {
+        i += found;
+        if bytes[i] != b'#' {
+            return i;
+        }
+        let run = bytes[i..].iter().take(7).take_while(|b| **b == b'#').count();
+        if run < 7 && bytes.get(i + run) == Some(&b' ') {
+            return i;
+        }
+        i += run;
+    }
+    bytes.len()
+}
 
 #[cfg(test)]
 mod tests {
@@ -140,4 +356,18 @@ mod tests {
             println!("{:?}", token.unwrap())
         }
     }
+
+    #[test]
+    fn tokens_carry_spans() {
+        let mut tokenizer = Tokenizer::new("## 标题\r\n😊#a".as_bytes());
+        let mut spans = Vec::new();
+        while let Some(res) = tokenizer.next_spanned() {
+            let (_, span) = res.unwrap();
+            spans.push((span.start.offset, span.end.offset, span.end.line, span.end.column, span.end.utf16_column));
+        }
+        assert_eq!(
+            spans,
+            vec![(0, 3, 1, 4, 4), (3, 9, 1, 6, 6), (9, 11, 2, 1, 1), (11, 15, 2, 2, 3), (15, 16, 2, 3, 4), (16, 17, 2, 4, 5)]
+        );
+    }
 }
\ No newline at end of file
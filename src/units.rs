@@ -0,0 +1,95 @@
+//! 行内和块级解析共用的一些 `&str` 操作。下标按字节计算，查找的都是 ASCII 符号，
+//! 所以找到的位置总在字符边界上。
+use u8unit::U8Unit;
+
+/// 把分词器逐字符读出的内容接到 `out` 后面，无效的 UTF-8 换成 U+FFFD
+pub fn push_units(out: &mut String, units: &[U8Unit]) {
+    for unit in units {
+        out.push_str(std::str::from_utf8(unit.as_bytes()).unwrap_or("\u{FFFD}"));
+    }
+}
+
+/// 空格和制表符
+pub fn is_blank_byte(b: u8) -> bool {
+    b == b' ' || b == b'\t'
+}
+
+pub fn count_leading(text: &str, byte: u8) -> usize {
+    text.bytes().take_while(|b| *b == byte).count()
+}
+
+pub fn trim_start(text: &str) -> &str {
+    text.trim_start_matches([' ', '\t'])
+}
+
+pub fn trim_end(text: &str) -> &str {
+    text.trim_end_matches([' ', '\t'])
+}
+
+pub fn trim(text: &str) -> &str {
+    trim_end(trim_start(text))
+}
+
+pub fn is_blank(text: &str) -> bool {
+    text.bytes().all(is_blank_byte)
+}
+
+/// 从 `from` 开始查找 `pat`
+pub fn find(text: &str, pat: &str, from: usize) -> Option<usize> {
+    text[from..].find(pat).map(|i| from + i)
+}
+
+/// `i` 处字符之后的位置
+pub fn next_char(text: &str, i: usize) -> usize {
+    i + text[i..].chars().next().map_or(1, char::len_utf8)
+}
+
+/// `i` 处的字符
+pub fn char_at(text: &str, i: usize) -> Option<char> {
+    text.get(i..)?.chars().next()
+}
+
+/// `i` 之前的一个字符
+pub fn char_before(text: &str, i: usize) -> Option<char> {
+    text[..i].chars().next_back()
+}
+
+/// `open` 处是 `[`，返回与之匹配的 `]` 的位置，支持嵌套和 `\]`
+pub fn bracket_end(text: &str, open: usize) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = open + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => {
+                i += 2;
+                continue;
+            }
+            b'[' => depth += 1,
+            b']' if depth == 0 => return Some(i),
+            b']' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// 按 `\n`、`\r\n`、`\r` 切分，返回每一行的内容和其后的换行符，最后一行没有换行符时为空
+pub fn lines(text: &str) -> impl Iterator<Item = (&str, &str)> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest.find(['\r', '\n']).unwrap_or(rest.len());
+        let ending = match &rest.as_bytes()[end..] {
+            [b'\r', b'\n', ..] => 2,
+            [] => 0,
+            _ => 1,
+        };
+        let (line, ending) = (&rest[..end], &rest[end..end + ending]);
+        rest = &rest[end + ending.len()..];
+        Some((line, ending))
+    })
+}
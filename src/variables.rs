@@ -0,0 +1,166 @@
+//! 变量替换：正文和链接地址中的 `{{ name }}` 替换为变量的值。
+//!
+//! 值来自 `VariableOptions::values`（引擎配置或命令行的 `--var key=value`）以及文档开头
+//! `---` 围起来的 front matter，front matter 中的同名变量优先。替换后的值按普通文本输出，
+//! 不再经过行内规则。`{{#include path}}` 以 `#` 开头，不会被当成变量。
+use std::{borrow::Cow, collections::HashMap};
+
+use crate::units;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VariableOptions {
+    pub values: HashMap<String, String>,
+    pub undefined: UndefinedVariable,
+    /// 行内代码和代码块中也替换
+    pub in_code: bool,
+}
+
+/// 遇到未定义的变量时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UndefinedVariable {
+    /// 原样保留 `{{ name }}`
+    #[default]
+    Leave,
+    /// 替换为空
+    Empty,
+    /// 原样保留并记录一条诊断信息，交给 `ErrorHandler`
+    Error,
+}
+
+/// `text[i..]` 以 `{{ name }}` 开头时返回变量名和结束位置
+pub fn find(text: &str, i: usize) -> Option<(String, usize)> {
+    if !text[i..].starts_with("{{") {
+        return None;
+    }
+    let bytes = text.as_bytes();
+    let skip_blank = |mut j: usize| {
+        while bytes.get(j).is_some_and(|b| units::is_blank_byte(*b)) {
+            j += 1;
+        }
+        j
+    };
+    let start = skip_blank(i + 2);
+    let len = bytes[start..].iter().take_while(|b| is_name_byte(**b)).count();
+    if len == 0 || !bytes[start].is_ascii_alphabetic() && bytes[start] != b'_' {
+        return None;
+    }
+    let end = skip_blank(start + len);
+    if !text[end..].starts_with("}}") {
+        return None;
+    }
+    Some((text[start..start + len].to_string(), end + 2))
+}
+
+fn is_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'-' || b == b'.'
+}
+
+/// 替换 `text` 中的变量，`undefined` 收集未定义的变量名；没有变量时不复制
+pub fn expand<'a>(text: &'a str, options: &VariableOptions, undefined: &mut Vec<String>) -> Cow<'a, str> {
+    let mut out = String::new();
+    let mut start = 0;
+    let mut i = 0;
+    while let Some(found) = units::find(text, "{{", i) {
+        i = found;
+        let Some((name, end)) = find(text, i) else {
+            i += 1;
+            continue;
+        };
+        let value = options.values.get(&name);
+        if value.is_none() && options.undefined != UndefinedVariable::Empty {
+            if options.undefined == UndefinedVariable::Error {
+                undefined.push(name);
+            }
+            i = end;
+            continue;
+        }
+        out.push_str(&text[start..i]);
+        if let Some(value) = value {
+            out.push_str(value);
+        }
+        i = end;
+        start = end;
+    }
+    if start == 0 {
+        return Cow::Borrowed(text);
+    }
+    out.push_str(&text[start..]);
+    Cow::Owned(out)
+}
+
+/// 单独一行的 `---`，front matter 的开始和结束；结束也可以用 `...`
+pub fn is_front_matter_fence(line: &str, open: bool) -> bool {
+    let line = units::trim_end(line);
+    line == "---" || !open && line == "..."
+}
+
+/// front matter 中的 `key: value`，值两侧的引号会被去掉；列表、嵌套等其它写法忽略
+pub fn parse_front_matter_line(line: &str) -> Option<(String, String)> {
+    let colon = line.find(':')?;
+    let key = units::trim_end(&line[..colon]);
+    if key.is_empty() || units::is_blank_byte(line.as_bytes()[0]) || !key.bytes().all(is_name_byte) {
+        return None;
+    }
+    let value = units::trim(&line[colon + 1..]);
+    let value = ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+        .unwrap_or(value);
+    Some((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{options::Options, parser::{tests::render_with_diagnostics, ParserImpl}};
+
+    #[test]
+    fn expand_and_front_matter() {
+        let mut options = VariableOptions::default();
+        options.values.insert("version".to_string(), "1.2".to_string());
+        let mut undefined = Vec::new();
+        let text = "v{{version}} / {{ missing }} / {{#include a.md}}";
+        let expand_with = |options: &VariableOptions, undefined: &mut Vec<String>| {
+            expand(text, options, undefined).into_owned()
+        };
+        assert_eq!(expand_with(&options, &mut undefined), "v1.2 / {{ missing }} / {{#include a.md}}");
+        assert!(undefined.is_empty());
+
+        options.undefined = UndefinedVariable::Empty;
+        assert_eq!(expand_with(&options, &mut undefined), "v1.2 /  / {{#include a.md}}");
+        options.undefined = UndefinedVariable::Error;
+        expand_with(&options, &mut undefined);
+        assert_eq!(undefined, vec!["missing"]);
+
+        let line = "title: \"发布说明\"";
+        assert_eq!(parse_front_matter_line(line), Some(("title".to_string(), "发布说明".to_string())));
+        assert!(parse_front_matter_line("  - item: x").is_none());
+        assert!(is_front_matter_fence("--- ", true));
+        assert!(!is_front_matter_fence("...", true));
+    }
+
+    #[test]
+    fn variables_from_front_matter_and_options() {
+        let mut variables = VariableOptions {
+            undefined: UndefinedVariable::Error,
+            ..VariableOptions::default()
+        };
+        variables.values.insert("host".to_string(), "example.com".to_string());
+        variables.values.insert("version".to_string(), "0.1".to_string());
+        let options = Options {
+            variables: Some(variables),
+            ..Options::default()
+        };
+        let src = "---\nversion: \"2.0\"\n# 注释\n---\n\
+                   v{{ version }} [下载](https://{{host}}/v{{version}}) `{{ version }}` {{ nope }}\n\
+                   [说明]({{ host }}/z)\n";
+        let (html, diagnostics) = render_with_diagnostics(ParserImpl::new(src.as_bytes()), &options);
+        assert_eq!(
+            html,
+            "<p>v2.0 <a href=\"https://example.com/v2.0\">下载</a> <code>{{ version }}</code> {{ nope }}</p>\
+             <p><a href=\"example.com/z\">说明</a></p>"
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((diagnostics[0].line_num, &*diagnostics[0].msg), (5, "undefined variable: nope"));
+    }
+}
@@ -0,0 +1,262 @@
+//! 遍历和改写文档树：只读的 `Visitor`、原地修改的 `VisitMut` 以及按值重建的 `Fold`。
+//!
+//! 默认实现会递归访问所有子节点，只需要覆盖关心的方法；覆盖后想继续向下遍历时调用同名的
+//! `walk_*` / `fold_*` 函数。
+use crate::ast::{Block, BlockKind, Document, Inline, InlineKind};
+
+pub trait Visitor {
+    fn visit_document(&mut self, doc: &Document) {
+        walk_document(self, doc);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_inline(&mut self, inline: &Inline) {
+        walk_inline(self, inline);
+    }
+}
+
+pub fn walk_document<V: Visitor + ?Sized>(v: &mut V, doc: &Document) {
+    for block in &doc.blocks {
+        v.visit_block(block);
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(v: &mut V, block: &Block) {
+    match &block.kind {
+        BlockKind::Paragraph { children, .. } | BlockKind::Heading { children, .. } => {
+            children.iter().for_each(|inline| v.visit_inline(inline))
+        }
+        BlockKind::LeafDirective(node) => node.children.iter().for_each(|inline| v.visit_inline(inline)),
+        BlockKind::Alert { children, .. } => children.iter().for_each(|block| v.visit_block(block)),
+        BlockKind::Directive(node) => node.children.iter().for_each(|block| v.visit_block(block)),
+        BlockKind::CodeBlock { .. } | BlockKind::Math(_) | BlockKind::TocMarker | BlockKind::BlankLine => {}
+    }
+}
+
+pub fn walk_inline<V: Visitor + ?Sized>(v: &mut V, inline: &Inline) {
+    match &inline.kind {
+        InlineKind::Emphasis { children, .. } | InlineKind::Link { children, .. } => {
+            children.iter().for_each(|inline| v.visit_inline(inline))
+        }
+        InlineKind::Directive(node) => node.children.iter().for_each(|inline| v.visit_inline(inline)),
+        _ => {}
+    }
+}
+
+pub trait VisitMut {
+    fn visit_document_mut(&mut self, doc: &mut Document) {
+        walk_document_mut(self, doc);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_inline_mut(&mut self, inline: &mut Inline) {
+        walk_inline_mut(self, inline);
+    }
+}
+
+pub fn walk_document_mut<V: VisitMut + ?Sized>(v: &mut V, doc: &mut Document) {
+    for block in &mut doc.blocks {
+        v.visit_block_mut(block);
+    }
+}
+
+pub fn walk_block_mut<V: VisitMut + ?Sized>(v: &mut V, block: &mut Block) {
+    match &mut block.kind {
+        BlockKind::Paragraph { children, .. } | BlockKind::Heading { children, .. } => {
+            children.iter_mut().for_each(|inline| v.visit_inline_mut(inline))
+        }
+        BlockKind::LeafDirective(node) => node.children.iter_mut().for_each(|inline| v.visit_inline_mut(inline)),
+        BlockKind::Alert { children, .. } => children.iter_mut().for_each(|block| v.visit_block_mut(block)),
+        BlockKind::Directive(node) => node.children.iter_mut().for_each(|block| v.visit_block_mut(block)),
+        BlockKind::CodeBlock { .. } | BlockKind::Math(_) | BlockKind::TocMarker | BlockKind::BlankLine => {}
+    }
+}
+
+pub fn walk_inline_mut<V: VisitMut + ?Sized>(v: &mut V, inline: &mut Inline) {
+    match &mut inline.kind {
+        InlineKind::Emphasis { children, .. } | InlineKind::Link { children, .. } => {
+            children.iter_mut().for_each(|inline| v.visit_inline_mut(inline))
+        }
+        InlineKind::Directive(node) => node.children.iter_mut().for_each(|inline| v.visit_inline_mut(inline)),
+        _ => {}
+    }
+}
+
+/// 按值重建文档，一个节点可以替换为零个或多个节点，例如删掉所有图片
+pub trait Fold {
+    fn fold_document(&mut self, doc: Document) -> Document {
+        fold_document(self, doc)
+    }
+
+    fn fold_block(&mut self, block: Block) -> Vec<Block> {
+        vec![fold_block(self, block)]
+    }
+
+    fn fold_inline(&mut self, inline: Inline) -> Vec<Inline> {
+        vec![fold_inline(self, inline)]
+    }
+}
+
+pub fn fold_document<F: Fold + ?Sized>(f: &mut F, doc: Document) -> Document {
+    Document {
+        blocks: fold_blocks(f, doc.blocks),
+        ..doc
+    }
+}
+
+/// 折叠 `block` 的子节点，`block` 本身保留
+pub fn fold_block<F: Fold + ?Sized>(f: &mut F, block: Block) -> Block {
+    let kind = match block.kind {
+        BlockKind::Paragraph { attrs, children } => BlockKind::Paragraph {
+            attrs,
+            children: fold_inlines(f, children),
+        },
+        BlockKind::Heading { level, attrs, children } => BlockKind::Heading {
+            level,
+            attrs,
+            children: fold_inlines(f, children),
+        },
+        BlockKind::Alert { kind, title, attrs, children } => BlockKind::Alert {
+            kind,
+            title,
+            attrs,
+            children: fold_blocks(f, children),
+        },
+        BlockKind::Directive(mut node) => {
+            node.children = fold_blocks(f, node.children);
+            BlockKind::Directive(node)
+        }
+        BlockKind::LeafDirective(mut node) => {
+            node.children = fold_inlines(f, node.children);
+            BlockKind::LeafDirective(node)
+        }
+        kind => kind,
+    };
+    Block { kind, ..block }
+}
+
+/// 折叠 `inline` 的子节点，`inline` 本身保留
+pub fn fold_inline<F: Fold + ?Sized>(f: &mut F, inline: Inline) -> Inline {
+    let kind = match inline.kind {
+        InlineKind::Emphasis { level, children } => InlineKind::Emphasis {
+            level,
+            children: fold_inlines(f, children),
+        },
+        InlineKind::Link { dest, title, attrs, children } => InlineKind::Link {
+            dest,
+            title,
+            attrs,
+            children: fold_inlines(f, children),
+        },
+        InlineKind::Directive(mut node) => {
+            node.children = fold_inlines(f, node.children);
+            InlineKind::Directive(node)
+        }
+        kind => kind,
+    };
+    Inline { kind, ..inline }
+}
+
+fn fold_blocks<F: Fold + ?Sized>(f: &mut F, blocks: Vec<Block>) -> Vec<Block> {
+    blocks.into_iter().flat_map(|block| f.fold_block(block)).collect()
+}
+
+fn fold_inlines<F: Fold + ?Sized>(f: &mut F, inlines: Vec<Inline>) -> Vec<Inline> {
+    inlines.into_iter().flat_map(|inline| f.fold_inline(inline)).collect()
+}
+
+/// 在解析和输出之间修改文档，由 `MrakdownEngine::register_transform` 注册
+pub trait Transform {
+    fn transform(&mut self, doc: &mut Document);
+}
+
+impl<F: FnMut(&mut Document)> Transform for F {
+    fn transform(&mut self, doc: &mut Document) {
+        self(doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::MrakdownEngine,
+        error_handler::ErrorHandlerImpl,
+        options::Options,
+        parser::{AstParser, ParserImpl},
+        schema::HtmlSchema,
+    };
+
+    struct CodeBlocks(Vec<String>);
+
+    impl Visitor for CodeBlocks {
+        fn visit_block(&mut self, block: &Block) {
+            if let BlockKind::CodeBlock { code, .. } = &block.kind {
+                self.0.push(code.clone());
+            }
+            walk_block(self, block);
+        }
+    }
+
+    struct BumpHeadings;
+
+    impl VisitMut for BumpHeadings {
+        fn visit_block_mut(&mut self, block: &mut Block) {
+            if let BlockKind::Heading { level, .. } = &mut block.kind {
+                *level = (*level + 1).min(6);
+            }
+            walk_block_mut(self, block);
+        }
+    }
+
+    struct StripImages;
+
+    impl Fold for StripImages {
+        fn fold_inline(&mut self, inline: Inline) -> Vec<Inline> {
+            match inline.kind {
+                InlineKind::Image { .. } => Vec::new(),
+                _ => vec![fold_inline(self, inline)],
+            }
+        }
+    }
+
+    #[test]
+    fn visit_and_fold() {
+        let src = "# 标题\n> [!NOTE]\n> ```\n> a\n> ```\n*看 ![图](a.png)*\n";
+        let mut doc = ParserImpl::new(src.as_bytes()).parse_to_ast(&Options::default()).unwrap();
+
+        let mut code = CodeBlocks(Vec::new());
+        code.visit_document(&doc);
+        assert_eq!(code.0, vec!["a\n"]);
+
+        BumpHeadings.visit_document_mut(&mut doc);
+        assert!(matches!(doc.blocks[0].kind, BlockKind::Heading { level: 2, .. }));
+
+        let doc = StripImages.fold_document(doc);
+        let BlockKind::Paragraph { children, .. } = &doc.blocks.last().unwrap().kind else { unreachable!() };
+        let InlineKind::Emphasis { children, .. } = &children[0].kind else { unreachable!() };
+        assert_eq!(children.len(), 1);
+        assert!(matches!(&children[0].kind, InlineKind::Text(text) if text == "看 "));
+    }
+
+    #[test]
+    fn engine_runs_transforms() {
+        let mut engine = MrakdownEngine::new();
+        engine.register_transform(|doc: &mut Document| BumpHeadings.visit_document_mut(doc));
+        engine.register_transform(|doc: &mut Document| *doc = StripImages.fold_document(std::mem::take(doc)));
+        let mut out = Vec::new();
+        engine.start_with_transforms(
+            ParserImpl::new("# 标题\n![图](a.png)文字\n".as_bytes()),
+            &mut out,
+            ErrorHandlerImpl::new(),
+            HtmlSchema::new(),
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), "<h2>标题</h2><br><p>文字</p>");
+    }
+}
@@ -0,0 +1,85 @@
+//! `[[Page]]`、`[[Page|label]]`、`[[Page#Heading]]` 形式的维基链接，
+//! 页面名到 URL 的转换交给用户提供的 `WikiResolver`。
+use std::fmt;
+
+use crate::units;
+
+pub trait WikiResolver {
+    /// 返回页面的 URL，页面不存在时返回 `None`
+    fn resolve(&self, page: &str) -> Option<String>;
+}
+
+impl<F> WikiResolver for F
+where
+    F: Fn(&str) -> Option<String>,
+{
+    fn resolve(&self, page: &str) -> Option<String> {
+        self(page)
+    }
+}
+
+impl fmt::Debug for dyn WikiResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("WikiResolver")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct WikiLink<'a> {
+    /// 为空时指向当前页面，例如 `[[#Heading]]`
+    pub page: &'a str,
+    pub heading: Option<&'a str>,
+    /// 没有 `|label` 时为 `[[...]]` 中的原文
+    pub label: &'a str,
+}
+
+/// `i` 处是 `[[`，返回链接和 `]]` 之后的位置
+pub fn parse(line: &str, i: usize) -> Option<(WikiLink<'_>, usize)> {
+    if !line[i..].starts_with("[[") {
+        return None;
+    }
+    let start = i + 2;
+    let end = units::find(line, "]]", start)?;
+    let inner = &line[start..end];
+    if units::is_blank(inner) || inner.contains(['[', ']']) {
+        return None;
+    }
+    let (target, label) = match inner.find('|') {
+        Some(bar) => (&inner[..bar], units::trim(&inner[bar + 1..])),
+        None => (inner, units::trim(inner)),
+    };
+    let (page, heading) = match target.find('#') {
+        Some(hash) => (&target[..hash], Some(units::trim(&target[hash + 1..]))),
+        None => (target, None),
+    };
+    let link = WikiLink {
+        page: units::trim(page),
+        heading,
+        label,
+    };
+    Some((link, end + 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{options::Options, parser::{tests::render_with_diagnostics, ParserImpl}};
+
+    #[test]
+    fn wiki_links_use_resolver() {
+        let options = Options {
+            wiki_resolver: Some(Box::new(|page: &str| (page == "Home Page").then(|| "/wiki/home".to_string()))),
+            ..Options::default()
+        };
+        let src = "x\n[[Home Page]] [[Home Page#Getting Started|start]] [[#Top]] [[Nope]] [[]]";
+        let (html, diagnostics) = render_with_diagnostics(ParserImpl::new(src.as_bytes()), &options);
+        assert_eq!(
+            html,
+            "<p>x</p><p><a href=\"/wiki/home\">Home Page</a> \
+             <a href=\"/wiki/home#getting-started\">start</a> \
+             <a href=\"#top\">#Top</a> <a href=\"#\" class=\"missing\">Nope</a> [[]]</p>"
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line_num, 2);
+        assert_eq!(diagnostics[0].msg, "wiki page not found: Nope");
+    }
+}
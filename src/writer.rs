@@ -24,4 +24,25 @@ impl<W: Write> Writer for WriterImpl<W> {
     fn flush(&mut self) -> IoResult<()> {
         self.buf_writer.flush()
     }
-}
\ No newline at end of file
+}
+
+impl Writer for Vec<u8> {
+    fn write(&mut self, data: &[u8]) -> IoResult<usize> {
+        self.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+impl<W: Writer + ?Sized> Writer for &mut W {
+    fn write(&mut self, data: &[u8]) -> IoResult<usize> {
+        (**self).write(data)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        (**self).flush()
+    }
+}
@@ -1,13 +1,70 @@
-use std::{io::Read, usize};
+use std::io::Read;
 use std::io::Result as IoResult;
 
-const BUF_SIZE: usize = 512;
+// 每次 `read` 的大小。`next_run` 只在缓冲区内找连续的字符，缓冲区越大，
+// 一段文本被缓冲区末尾截断的次数越少；512 字节时中文段落经常被切成几段
+const BUF_SIZE: usize = 8192;
 pub struct CharIterator<R: Read> {
     r: R,
     buf: [u8; BUF_SIZE],
     cursor: usize,
     cur_len: usize,
-    remain: Remain
+    remain: Remain,
+    // 下一个字符的位置
+    pos: Position,
+    after_cr: bool,
+}
+
+/// 源文件中的位置：字节偏移从 0 开始，行和列从 1 开始，
+/// 列分别按字符、UTF-16 编码单元和字节计数
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub offset: usize,
+    pub line: u32,
+    pub column: u32,
+    pub utf16_column: u32,
+    pub byte_column: u32,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+            utf16_column: 1,
+            byte_column: 1,
+        }
+    }
+}
+
+impl Position {
+    /// 越过一个字符；`\r\n` 中的 `\n` 传入 `after_cr = true`，只算一次换行
+    pub fn advance(&mut self, unit: &U8Unit, after_cr: bool) {
+        self.offset += unit.byte_len();
+        if unit == b'\n' || unit == b'\r' {
+            if !(after_cr && unit == b'\n') {
+                self.line += 1;
+            }
+            self.column = 1;
+            self.utf16_column = 1;
+            self.byte_column = 1;
+        } else {
+            self.column += 1;
+            self.utf16_column += if unit.byte_len() == 4 { 2 } else { 1 };
+            self.byte_column += unit.byte_len() as u32;
+        }
+    }
+
+    /// 越过一段不含换行的文本
+    pub fn advance_text(&mut self, text: &str) {
+        self.offset += text.len();
+        self.byte_column += text.len() as u32;
+        for c in text.chars() {
+            self.column += 1;
+            self.utf16_column += c.len_utf16() as u32;
+        }
+    }
 }
 /// utf_8 bytes
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -29,6 +86,51 @@ impl<'a> From<&'a U8Unit> for &'a [u8] {
     }
 }
 
+impl U8Unit {
+    pub fn as_bytes(&self) -> &[u8] {
+        self.into()
+    }
+
+    pub fn byte_len(&self) -> usize {
+        self.as_bytes().len()
+    }
+
+    pub fn is_ascii(&self) -> bool {
+        matches!(self, U8Unit::OneByte(_))
+    }
+
+    // ' ' 和 '\t'
+    pub fn is_blank(&self) -> bool {
+        matches!(self, U8Unit::OneByte([b' ']) | U8Unit::OneByte([b'\t']))
+    }
+
+    pub fn to_char(&self) -> char {
+        std::str::from_utf8(self.as_bytes())
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
+}
+
+impl From<u8> for U8Unit {
+    fn from(value: u8) -> Self {
+        U8Unit::OneByte([value])
+    }
+}
+
+impl From<char> for U8Unit {
+    fn from(value: char) -> Self {
+        let mut buf = [0u8; 4];
+        let bytes = value.encode_utf8(&mut buf).as_bytes();
+        match bytes.len() {
+            1 => U8Unit::OneByte([bytes[0]]),
+            2 => U8Unit::TwoByte([bytes[0], bytes[1]]),
+            3 => U8Unit::ThreeByte([bytes[0], bytes[1], bytes[2]]),
+            _ => U8Unit::FourByte([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+}
+
 impl PartialEq<u8> for U8Unit{
     fn eq(&self, other: &u8) -> bool {
         match self {
@@ -86,10 +188,17 @@ impl<R: Read> CharIterator<R> {
             buf: [0; BUF_SIZE],
             cursor: 0, 
             cur_len: usize::MAX, 
-            remain: Remain::default()
+            remain: Remain::default(),
+            pos: Position::default(),
+            after_cr: false,
         }
     }
 
+    /// 下一个字符的位置
+    pub fn position(&self) -> Position {
+        self.pos
+    }
+
     //buf is satisfied 
     fn cur_u8bytes(&mut self, chr_len: usize) -> U8Unit {
 
@@ -206,6 +315,43 @@ impl <R: Read> Iterator for CharIterator<R> {
     type Item = IoResult<U8Unit>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let res = self.next_unit();
+        if let Some(Ok(unit)) = &res {
+            self.pos.advance(unit, self.after_cr);
+            self.after_cr = unit == b'\r';
+        }
+        res
+    }
+}
+
+impl<R: Read> CharIterator<R> {
+    /// 从缓冲区中读出一段连续的字符追加到 `out`，到 `stop` 中的字节或缓冲区末尾为止，
+    /// 返回读出的字符数；停下的字节和缓冲区末尾不完整的字符留给 `next`。
+    /// `find_any` 只负责找到终点，其间的字符仍然逐个解码成 `U8Unit`
+    pub fn next_run(&mut self, out: &mut Vec<U8Unit>, stop: &[u8]) -> usize {
+        if self.cur_len == usize::MAX || !self.remain.is_empty() {
+            return 0;
+        }
+        let start = self.cursor;
+        let end = find_any(&self.buf[start..self.cur_len], stop).map_or(self.cur_len, |i| start + i);
+        let count = out.len();
+        while self.cursor < end {
+            let len = utf8_len(self.get_byte(self.cursor));
+            if len == 0 || self.cursor + len > end {
+                break;
+            }
+            let unit = self.cur_u8bytes(len);
+            self.pos.advance(&unit, false);
+            out.push(unit);
+        }
+        let count = out.len() - count;
+        if count > 0 {
+            self.after_cr = false;
+        }
+        count
+    }
+
+    fn next_unit(&mut self) -> Option<IoResult<U8Unit>> {
 
         loop {
 
@@ -244,6 +390,25 @@ impl <R: Read> Iterator for CharIterator<R> {
     }
 }
 
+const LO: u64 = 0x0101_0101_0101_0101;
+const HI: u64 = 0x8080_8080_8080_8080;
+
+/// `haystack` 中第一个属于 `needles` 的字节，每次按 8 个字节一起比较
+pub fn find_any(haystack: &[u8], needles: &[u8]) -> Option<usize> {
+    // 字中有为 0 的字节时结果非 0
+    let has_zero = |x: u64| x.wrapping_sub(LO) & !x & HI != 0;
+    let mut chunks = haystack.chunks_exact(8);
+    let mut offset = 0;
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        if needles.iter().any(|&n| has_zero(word ^ (LO * n as u64))) {
+            break;
+        }
+        offset += 8;
+    }
+    haystack[offset..].iter().position(|b| needles.contains(b)).map(|i| offset + i)
+}
+
 /**
  *
  * 1. 如果首字节的最高位是0，那这个字符是单字节（0xxxxxxx）。
@@ -272,6 +437,56 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn positions_count_chars_and_utf16() {
+        let mut iter = CharIterator::new("a中😊\r\nb\rc".as_bytes());
+        let mut positions = Vec::new();
+        while iter.next().is_some() {
+            let pos = iter.position();
+            positions.push((pos.offset, pos.line, pos.column, pos.utf16_column, pos.byte_column));
+        }
+        assert_eq!(
+            positions,
+            vec![
+                (1, 1, 2, 2, 2),
+                (4, 1, 3, 3, 5),
+                (8, 1, 4, 5, 9),
+                (9, 2, 1, 1, 1),
+                (10, 2, 1, 1, 1),
+                (11, 2, 2, 2, 2),
+                (12, 3, 1, 1, 1),
+                (13, 3, 2, 2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn runs_stop_at_special_bytes() {
+        let text = "普通文本 plain text 😊#标题\n下一行";
+        assert_eq!(find_any(text.as_bytes(), b"#\n"), text.find('#'));
+        assert_eq!(find_any(b"abcdefghijklmnop", b"\n"), None);
+        assert_eq!(find_any(b"abcdefghijk\n", b"#\n"), Some(11));
+
+        // 输入比缓冲区长，字符会跨过缓冲区末尾
+        let long = text.repeat(400);
+        let mut iter = CharIterator::new(long.as_bytes());
+        let mut units = Vec::new();
+        while let Some(unit) = iter.next() {
+            units.push(unit.unwrap());
+            iter.next_run(&mut units, b"#\n");
+        }
+        let read: String = units.iter().map(U8Unit::to_char).collect();
+        assert_eq!(read, long);
+        assert_eq!(iter.position().offset, long.len());
+        assert_eq!(iter.position().line, 401);
+
+        let mut iter = CharIterator::new(text.as_bytes());
+        let mut units = Vec::new();
+        iter.next();
+        assert_eq!(iter.next_run(&mut units, b"#\n"), text[..text.find('#').unwrap()].chars().count() - 1);
+        assert_eq!(iter.next().map(|u| u.unwrap()), Some(U8Unit::from(b'#')));
+    }
+
     #[test]
     fn char_iterator_works() {
         let file = File::open("../123.txt").unwrap();